@@ -41,7 +41,7 @@ fn camera_zoom_system(
     mut mouse_wheel_events: EventReader<MouseWheel>,
     mut projection_query: Query<&mut Transform, With<MainCamera>>,
 ) {
-    for event in mouse_wheel_events.iter() {
+    for event in mouse_wheel_events.read() {
         let event: &MouseWheel = event;
         for mut transform in projection_query.iter_mut() {
             #[cfg(target_arch = "wasm32")]
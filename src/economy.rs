@@ -0,0 +1,297 @@
+use std::ops::Not;
+
+use bevy::prelude::*;
+
+use crate::pause::AppState;
+use crate::planet::Planet;
+use crate::util::OncePerSecond;
+use crate::v2::commodity::Commodity;
+use crate::v2::demand::Demand;
+use crate::v2::inventory::{Amount, Inventory};
+use crate::v2::ledger::{Ledger, Wallet};
+use crate::v2::market::Market;
+use crate::v2::order_book::{OrderBook, Side};
+use crate::v2::store::{Credits, OrderMode, Store};
+
+/// How long limit orders stay in a book before falling back to the store
+pub(crate) const ORDER_TTL: f32 = 3.;
+
+pub struct EconomyPlugin;
+
+impl Plugin for EconomyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Ledger>();
+        app.init_resource::<Market>();
+        app.add_systems(PreUpdate, index_market);
+        app.add_systems(Update, audit_ledger);
+        app.add_systems(
+            Update,
+            (
+                update_consumption_rates,
+                expire_reservations,
+                clear_order_books,
+            )
+                .run_if(in_state(AppState::GameRunning)),
+        );
+    }
+}
+
+fn index_market(
+    mut market: ResMut<Market>,
+    stores: Query<(Entity, &Transform, &Store), With<Planet>>,
+) {
+    market.clear();
+    for (planet, transform, store) in stores.iter() {
+        market.add_store(planet, transform.translation.truncate(), store);
+    }
+}
+
+fn update_consumption_rates(
+    time: Res<Time>,
+    mut once_per_second: Local<OncePerSecond>,
+    mut stores: Query<&mut Store>,
+) {
+    if once_per_second.timer.tick(time.delta()).just_finished() {
+        for mut store in stores.iter_mut() {
+            store.end_consumption_period();
+        }
+    }
+}
+
+/// Frees up what ships reserved but never came for
+fn expire_reservations(
+    time: Res<Time>,
+    mut once_per_second: Local<OncePerSecond>,
+    mut stores: Query<&mut Store>,
+) {
+    if once_per_second.timer.tick(time.delta()).just_finished() {
+        for mut store in stores.iter_mut() {
+            store.remove_expired_reservations(time.elapsed_seconds());
+        }
+    }
+}
+
+fn clear_order_books(
+    time: Res<Time>,
+    mut once_per_second: Local<OncePerSecond>,
+    mut books: Query<(&mut OrderBook, &mut Store)>,
+    mut wallets: Query<&mut Wallet>,
+    mut inventories: Query<&mut Inventory>,
+    mut demands: Query<&mut Demand>,
+    mut ledger: ResMut<Ledger>,
+) {
+    if once_per_second
+        .timer
+        .tick(time.delta())
+        .just_finished()
+        .not()
+    {
+        return;
+    }
+    for (mut book, mut store) in books.iter_mut() {
+        for trade in book.match_orders() {
+            let receipt = trade.receipt;
+            // a trader that's gone can't settle, whoever's still around gets back what they put in.
+            // The store takes the goods of a seller that's gone, a buyer's money goes with them
+            if wallets.contains(trade.buyer).not() || wallets.contains(trade.seller).not() {
+                warn!("Couldn't settle {:?}, a trader is gone", receipt);
+                refund_escrow(
+                    &mut book.escrow,
+                    wallets.get_mut(trade.buyer).ok().as_deref_mut(),
+                    trade.bid_price * receipt.amount,
+                    &mut ledger,
+                );
+                if wallets.contains(trade.seller) {
+                    deliver(
+                        trade.seller,
+                        receipt.commodity,
+                        receipt.amount,
+                        &mut inventories,
+                    );
+                } else {
+                    let _ = store.give(receipt.commodity, receipt.amount);
+                }
+                continue;
+            }
+            let mut seller = wallets.get_mut(trade.seller).expect("Checked above");
+            ledger
+                .transfer(&mut book.escrow, &mut seller, receipt.total)
+                .expect("Buyer's money is in escrow");
+            let refund = (trade.bid_price - receipt.unit_price) * receipt.amount;
+            if refund > 0 {
+                let mut buyer = wallets.get_mut(trade.buyer).expect("Checked above");
+                ledger
+                    .transfer(&mut book.escrow, &mut buyer, refund)
+                    .expect("Buyer's money is in escrow");
+            }
+            deliver(
+                trade.buyer,
+                receipt.commodity,
+                receipt.amount,
+                &mut inventories,
+            );
+            satisfy(trade.buyer, receipt.commodity, receipt.amount, &mut demands);
+            debug!("Order book trade: {:?}", receipt);
+        }
+
+        // whatever the book couldn't fill in time goes to the store, if its quote is within the limit
+        for order in book.remove_expired(time.elapsed_seconds()) {
+            let Ok(mut wallet) = wallets.get_mut(order.trader) else {
+                // nobody to give it back to, the store takes the goods and the money goes with them
+                warn!(
+                    "{:?} order for {} {:?} expired after its trader was gone",
+                    order.side, order.amount, order.commodity
+                );
+                match order.side {
+                    Side::Buy => refund_escrow(
+                        &mut book.escrow,
+                        None,
+                        order.amount * order.price,
+                        &mut ledger,
+                    ),
+                    Side::Sell => {
+                        let _ = store.give(order.commodity, order.amount);
+                    }
+                }
+                continue;
+            };
+            match order.side {
+                Side::Buy => {
+                    ledger
+                        .transfer(&mut book.escrow, &mut wallet, order.amount * order.price)
+                        .expect("Buyer's money is in escrow");
+                    if let Some(receipt) = store.buy_from_store(
+                        order.commodity,
+                        order.amount,
+                        Some(order.price),
+                        OrderMode::Partial,
+                        &mut wallet,
+                        &mut ledger,
+                    ) {
+                        deliver(
+                            order.trader,
+                            receipt.commodity,
+                            receipt.amount,
+                            &mut inventories,
+                        );
+                        satisfy(
+                            order.trader,
+                            receipt.commodity,
+                            receipt.amount,
+                            &mut demands,
+                        );
+                    }
+                }
+                Side::Sell => {
+                    let sold = store
+                        .sell_to_store(
+                            order.commodity,
+                            order.amount,
+                            Some(order.price),
+                            OrderMode::Partial,
+                            &mut wallet,
+                            &mut ledger,
+                        )
+                        .map(|receipt| receipt.amount)
+                        .unwrap_or(0);
+                    if order.amount > sold {
+                        deliver(
+                            order.trader,
+                            order.commodity,
+                            order.amount - sold,
+                            &mut inventories,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Hands a buyer's escrowed money back, or takes it out of circulation if the buyer is gone
+fn refund_escrow(
+    escrow: &mut Wallet,
+    buyer: Option<&mut Wallet>,
+    amount: Credits,
+    ledger: &mut Ledger,
+) {
+    match buyer {
+        Some(buyer) => ledger.transfer(escrow, buyer, amount),
+        None => ledger.burn(escrow, amount),
+    }
+    .expect("Buyer's money is in escrow");
+}
+
+/// Traders without a hold, like a planet's population, consume what they get on the spot
+fn deliver(
+    trader: Entity,
+    commodity: Commodity,
+    amount: Amount,
+    inventories: &mut Query<&mut Inventory>,
+) {
+    match inventories.get_mut(trader) {
+        Ok(mut inventory) => {
+            if let Err(e) = inventory.add(commodity, amount) {
+                warn!("Lost {} {:?}, no room for it: {:?}", amount, commodity, e);
+            }
+        }
+        Err(_) => debug!("{} {:?} consumed", amount, commodity),
+    }
+}
+
+/// Counts what a trader got against its demand, whatever it didn't get it keeps wanting
+fn satisfy(trader: Entity, commodity: Commodity, amount: Amount, demands: &mut Query<&mut Demand>) {
+    if let Ok(mut demand) = demands.get_mut(trader) {
+        demand.bought(commodity, amount);
+    }
+}
+
+fn audit_ledger(
+    time: Res<Time>,
+    mut once_per_second: Local<OncePerSecond>,
+    ledger: Res<Ledger>,
+    wallets: Query<&Wallet>,
+    stores: Query<&Store>,
+    books: Query<&OrderBook>,
+) {
+    if once_per_second.timer.tick(time.delta()).just_finished() {
+        let wallets = wallets
+            .iter()
+            .chain(stores.iter().map(|store| &store.wallet))
+            .chain(books.iter().map(|book| &book.escrow));
+        if let Err(e) = ledger.audit(wallets) {
+            error!("Ledger doesn't add up: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escrow_goes_back_to_the_buyer() {
+        let mut ledger = Ledger::default();
+        let mut escrow = Wallet::default();
+        let mut buyer = Wallet::default();
+        ledger.mint(&mut escrow, 50);
+
+        refund_escrow(&mut escrow, Some(&mut buyer), 50, &mut ledger);
+
+        assert_eq!(buyer.credits(), 50);
+        assert_eq!(ledger.audit([&escrow, &buyer]), Ok(()));
+    }
+
+    #[test]
+    fn escrow_of_a_buyer_thats_gone_is_burnt() {
+        let mut ledger = Ledger::default();
+        let mut escrow = Wallet::default();
+        ledger.mint(&mut escrow, 50);
+
+        refund_escrow(&mut escrow, None, 50, &mut ledger);
+
+        assert_eq!(escrow.credits(), 0);
+        assert_eq!(ledger.money_supply(), 0);
+        assert_eq!(ledger.audit([&escrow]), Ok(()));
+    }
+}
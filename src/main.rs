@@ -3,6 +3,8 @@ use bevy_prototype_lyon::prelude::*;
 
 use crate::asset_loading::AssetLoadingPlugin;
 use crate::camera::CameraPlugin;
+use crate::economy::EconomyPlugin;
+use crate::orders::OrdersPlugin;
 use crate::pause::PausePlugin;
 use crate::planet::PlanetPlugin;
 use crate::ship::ShipPlugin;
@@ -12,6 +14,8 @@ use crate::unit_selection::SelectPlugin;
 mod asset_loading;
 mod camera;
 pub mod common_components;
+mod economy;
+mod orders;
 mod pause;
 mod planet;
 mod ship;
@@ -33,6 +37,7 @@ fn main() {
         }))
         .add_plugins((
             AssetLoadingPlugin,
+            EconomyPlugin,
             ShipPlugin,
             PlanetPlugin,
             CameraPlugin,
@@ -40,6 +45,7 @@ fn main() {
             UiPlugin,
             SelectPlugin,
             PausePlugin,
+            OrdersPlugin,
         ))
         .run();
 }
@@ -0,0 +1,206 @@
+use std::ops::Not;
+
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use strum::IntoEnumIterator;
+
+use crate::asset_loading::Fonts;
+use crate::camera::MainCamera;
+use crate::common_components::Name;
+use crate::planet::Planet;
+use crate::ship::Ship;
+use crate::unit_selection::Selectable;
+use crate::v2::commodity::Commodity;
+
+pub(crate) struct OrdersPlugin;
+
+impl Plugin for OrdersPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<OrderIssued>();
+        app.add_systems(Update, (open_order_menu_system, order_menu_button_system));
+    }
+}
+
+/// What the player can tell a ship to do at a planet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Order {
+    MoveHere,
+    /// Fill the hold with the commodity
+    Buy(Commodity),
+    /// Sell everything of the commodity in the hold
+    Sell(Commodity),
+    /// Wait until there's enough of the commodity for sale to fill the hold
+    WaitFor(Commodity),
+    Dock,
+    Undock,
+    /// Fly between home and the planet until told otherwise
+    PatrolFromHome,
+    SetHome,
+    ResumeAutopilot,
+    /// Standing orders, see `Automation`
+    Automatic,
+    Manual,
+    TradeOnly(Commodity),
+    StayNearHome,
+    /// Buy the commodity at home and sell it here, forever
+    ShuttleFromHome(Commodity),
+}
+
+impl std::fmt::Display for Order {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MoveHere => write!(f, "Move here"),
+            Self::Buy(commodity) => write!(f, "Buy {}", commodity),
+            Self::Sell(commodity) => write!(f, "Sell {}", commodity),
+            Self::WaitFor(commodity) => write!(f, "Wait for {}", commodity),
+            Self::Dock => write!(f, "Dock"),
+            Self::Undock => write!(f, "Undock"),
+            Self::PatrolFromHome => write!(f, "Patrol from home"),
+            Self::SetHome => write!(f, "Set home"),
+            Self::ResumeAutopilot => write!(f, "Resume autopilot"),
+            Self::Automatic => write!(f, "Automatic"),
+            Self::Manual => write!(f, "Manual"),
+            Self::TradeOnly(commodity) => write!(f, "Only trade {}", commodity),
+            Self::StayNearHome => write!(f, "Stay near home"),
+            Self::ShuttleFromHome(commodity) => write!(f, "Shuttle {} from home", commodity),
+        }
+    }
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub(crate) struct OrderIssued {
+    pub(crate) ship: Entity,
+    pub(crate) planet: Entity,
+    pub(crate) order: Order,
+}
+
+#[derive(Component)]
+struct OrderMenu;
+
+#[derive(Component)]
+struct OrderButton(OrderIssued);
+
+/// Right-clicking a planet with a ship selected opens the orders that ship can be given there
+#[allow(clippy::too_many_arguments)]
+fn open_order_menu_system(
+    mut commands: Commands,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mouse: Res<Input<MouseButton>>,
+    ships: Query<(Entity, &Selectable), With<Ship>>,
+    planets: Query<(Entity, &Transform, &Name), With<Planet>>,
+    menus: Query<Entity, With<OrderMenu>>,
+    buttons: Query<&Interaction, With<OrderButton>>,
+    fonts: Res<Fonts>,
+) {
+    let clicked_beside_menu = mouse.just_pressed(MouseButton::Left)
+        && buttons
+            .iter()
+            .all(|interaction| *interaction == Interaction::None);
+    if mouse.just_pressed(MouseButton::Right) || clicked_beside_menu {
+        for menu in menus.iter() {
+            commands.entity(menu).despawn_recursive();
+        }
+    }
+    if mouse.just_pressed(MouseButton::Right).not() {
+        return;
+    }
+
+    let Some(cursor) = windows.single().cursor_position() else {
+        return;
+    };
+    let (camera, camera_transform) = camera_query.single();
+    let Some(world_pos) = camera.viewport_to_world_2d(camera_transform, cursor) else {
+        return;
+    };
+    let Some((ship, _)) = ships.iter().find(|(_, selectable)| selectable.selected) else {
+        return;
+    };
+    let Some((planet, _, planet_name)) = planets
+        .iter()
+        .find(|(_, transform, _)| world_pos.distance(transform.translation.truncate()) < 50.)
+    else {
+        return;
+    };
+
+    let orders = [Order::MoveHere]
+        .into_iter()
+        .chain(Commodity::iter().map(Order::Buy))
+        .chain(Commodity::iter().map(Order::Sell))
+        .chain(Commodity::iter().map(Order::WaitFor))
+        .chain([Order::Dock, Order::Undock, Order::PatrolFromHome])
+        .chain([Order::SetHome, Order::ResumeAutopilot])
+        .chain([Order::Automatic, Order::Manual])
+        .chain(Commodity::iter().map(Order::TradeOnly))
+        .chain([Order::StayNearHome])
+        .chain(Commodity::iter().map(Order::ShuttleFromHome));
+    let text_style = TextStyle {
+        font: fonts.font.clone(),
+        font_size: 16.,
+        color: Color::BLACK,
+    };
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(cursor.x),
+                top: Val::Px(cursor.y),
+                flex_direction: FlexDirection::Column,
+                border: UiRect::all(Val::Px(2.)),
+                ..Default::default()
+            },
+            background_color: Color::rgb(0.65, 0.65, 0.65).into(),
+            z_index: ZIndex::Global(10),
+            ..Default::default()
+        })
+        .insert(OrderMenu)
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                planet_name.0.clone(),
+                text_style.clone(),
+            ));
+            for order in orders {
+                parent
+                    .spawn(ButtonBundle {
+                        style: Style {
+                            padding: UiRect::all(Val::Px(4.)),
+                            ..Default::default()
+                        },
+                        background_color: Color::WHITE.into(),
+                        ..Default::default()
+                    })
+                    .insert(OrderButton(OrderIssued {
+                        ship,
+                        planet,
+                        order,
+                    }))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(
+                            order.to_string(),
+                            text_style.clone(),
+                        ));
+                    });
+            }
+        });
+}
+
+#[allow(clippy::type_complexity)]
+fn order_menu_button_system(
+    mut commands: Commands,
+    mut buttons: Query<(&Interaction, &OrderButton, &mut BackgroundColor), Changed<Interaction>>,
+    menus: Query<Entity, With<OrderMenu>>,
+    mut orders: EventWriter<OrderIssued>,
+) {
+    for (interaction, button, mut background) in buttons.iter_mut() {
+        match interaction {
+            Interaction::Pressed => {
+                orders.send(button.0);
+                for menu in menus.iter() {
+                    commands.entity(menu).despawn_recursive();
+                }
+            }
+            Interaction::Hovered => *background = Color::GRAY.into(),
+            Interaction::None => *background = Color::WHITE.into(),
+        }
+    }
+}
@@ -1,27 +1,51 @@
+use std::collections::HashMap;
+use std::f32::consts::FRAC_PI_4;
+use std::ops::Not;
+
 use bevy::prelude::*;
 use bevy_prototype_lyon::{entity::ShapeBundle, prelude::*};
 
 use crate::asset_loading::Fonts;
 use crate::common_components::Name;
+use crate::economy::ORDER_TTL;
 use crate::pause::AppState;
-use crate::planet::NaturalResource::{FertileSoil, HydrogenGasVents};
 use crate::unit_selection::Selectable;
 use crate::util::OncePerSecond;
 use crate::v2::commodity::Commodity;
-use crate::v2::commodity::Commodity::{Fuel, HydrogenTanks};
-use crate::v2::store::Store;
+use crate::v2::commodity::Commodity::{Food, Fuel, HydrogenTanks};
+use crate::v2::demand::{Demand, DemandCurve};
+use crate::v2::deposit::{Deposit, Deposits};
+use crate::v2::docking::DockingPort;
+use crate::v2::inventory::{Amount, Inventory};
+use crate::v2::ledger::{Ledger, Wallet};
+use crate::v2::orbit::Orbit;
+use crate::v2::order_book::OrderBook;
+use crate::v2::population::{Population, FOOD_PER_THOUSAND};
+use crate::v2::production::{Facility, Recipe};
+use crate::v2::store::{Credits, OrderMode, Store};
 
 pub struct PlanetPlugin;
 
 impl Plugin for PlanetPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, planet_setup);
+        app.add_systems(
+            Update,
+            (advance_orbits, place_orbiting_bodies)
+                .chain()
+                .run_if(in_state(AppState::GameRunning)),
+        );
+        // have the planets in place before anyone looks at where they are
+        app.add_systems(PostStartup, place_orbiting_bodies);
         app.add_systems(
             Update,
             (
-                population_buys_food,
-                produce_commodities_from_natural_resources,
-                hydrogen_refinery_produces_fuel,
+                population_consumes,
+                population_lives,
+                run_facilities,
+                facilities_trade,
+                regenerate_deposits,
+                discover_deposits,
             )
                 .run_if(in_state(AppState::GameRunning)),
         );
@@ -31,56 +55,193 @@ impl Plugin for PlanetPlugin {
 #[derive(Component, Debug)]
 pub struct Planet;
 
-// todo should these be separate structs instead?
+/// Sits at the origin with everything orbiting it
 #[derive(Component)]
-pub struct PlanetaryResources {
-    resources: Vec<NaturalResource>,
+struct Star;
+
+/// What a thousand people earn every second, this is where new money enters the economy
+const INCOME_PER_THOUSAND: f32 = 0.5;
+/// Fraction a well fed population grows by every second
+const POPULATION_GROWTH: f32 = 0.002;
+/// Units of food people keep at home
+const PANTRY_SIZE: Amount = 100;
+/// What a facility starts out with to buy its first inputs and pay its first wages
+const FACILITY_CAPITAL: Credits = 200;
+/// Room a facility has for its inputs and outputs
+const FACILITY_STORAGE: Amount = 200;
+/// Cycles worth of inputs a facility keeps in stock
+const INPUT_CYCLES: Amount = 5;
+/// Odds of prospectors finding a new deposit on a planet, every second
+const DISCOVERY_CHANCE: f32 = 0.002;
+
+/// A kind of deposit prospectors can come across, and the facility that's opened to work it
+struct Prospect {
+    facility: &'static str,
+    commodity: Commodity,
+    /// Finds range from half to one and a half times this
+    size: f32,
+    regeneration: f32,
+    recipe: Recipe,
 }
 
-pub enum NaturalResource {
-    FertileSoil,
-    HydrogenGasVents,
+/// Deposits prospectors might come across, and their luck
+#[derive(Resource)]
+struct Prospects {
+    prospects: Vec<Prospect>,
+    rng: fastrand::Rng,
 }
 
-#[derive(Component)]
-struct HydrogenRefinery;
+impl Prospects {
+    /// One planet's worth of prospecting, what was found and how big it is
+    fn search(&mut self) -> Option<(&Prospect, f32)> {
+        if self.prospects.is_empty() || self.rng.f32() >= DISCOVERY_CHANCE {
+            return None;
+        }
+        let prospect = &self.prospects[self.rng.usize(..self.prospects.len())];
+        Some((prospect, prospect.size * (0.5 + self.rng.f32())))
+    }
+}
+
+fn planet_setup(mut commands: Commands, fonts: Res<Fonts>, mut ledger: ResMut<Ledger>) {
+    commands
+        .spawn((
+            ShapeBundle {
+                path: GeometryBuilder::build_as(&shapes::Circle {
+                    radius: 30.,
+                    center: Vec2::default(),
+                }),
+                ..Default::default()
+            },
+            Fill::color(Color::YELLOW),
+        ))
+        .insert(Star);
 
-fn planet_setup(mut commands: Commands, fonts: Res<Fonts>) {
+    let farm = Recipe {
+        inputs: vec![],
+        outputs: vec![(Food, 10)],
+        cycle_time: 1.,
+        wages: 10,
+        from_deposits: true,
+    };
+    let gas_vents = Recipe {
+        inputs: vec![],
+        outputs: vec![(HydrogenTanks, 20)],
+        cycle_time: 1.,
+        wages: 10,
+        from_deposits: true,
+    };
+    let refinery = Recipe {
+        inputs: vec![(HydrogenTanks, 1)],
+        outputs: vec![(Fuel, 1)],
+        cycle_time: 1.,
+        wages: 1,
+        from_deposits: false,
+    };
+    commands.insert_resource(Prospects {
+        prospects: vec![
+            Prospect {
+                facility: "Farm",
+                commodity: Food,
+                size: 2000.,
+                regeneration: 4.,
+                recipe: farm.clone(),
+            },
+            Prospect {
+                facility: "Gas vents",
+                commodity: HydrogenTanks,
+                size: 10000.,
+                regeneration: 0.,
+                recipe: gas_vents.clone(),
+            },
+        ],
+        rng: fastrand::Rng::new(),
+    });
+
+    // (orbital radius, seconds per revolution, starting angle, what it orbits), moons come after
+    // the planet they orbit. Population is in thousands, followed by the store's spread, what
+    // people buy, what's in the ground and the facilities as (name, recipe, throughput)
     let planets = vec![
         (
             "Terra",
-            Vec2::new(100., 100.),
+            (150., 220., FRAC_PI_4, None),
             Color::CYAN,
             20.,
+            10.,
+            0.1,
+            Demand::default()
+                .with(Food, DemandCurve::new(FOOD_PER_THOUSAND, 5, 0.5, 10))
+                .with(Fuel, DemandCurve::new(0.02, 10, 1.5, 15)),
+            vec![],
+            vec![],
+            true,
+        ),
+        (
+            "Luna",
+            (40., 30., 0., Some("Terra")),
+            Color::SILVER,
+            5.,
+            1.,
+            0.3,
+            Demand::default().with(Food, DemandCurve::new(FOOD_PER_THOUSAND, 5, 0.3, 9)),
+            vec![],
             vec![],
             false,
         ),
         (
             "Agri",
-            Vec2::new(-100., -100.),
+            (100., 120., -3. * FRAC_PI_4, None),
             Color::LIME_GREEN,
             10.,
-            vec![FertileSoil],
+            3.,
+            0.2,
+            Demand::default()
+                .with(Food, DemandCurve::new(FOOD_PER_THOUSAND, 3, 0.3, 6))
+                .with(Fuel, DemandCurve::new(0.03, 8, 1., 12)),
+            // fertile soil grows back when it's left alone
+            vec![Deposit::new(Food, 3000., 5.)],
+            vec![("Farm", farm, 1)],
             false,
         ),
         (
             "Hydro",
-            Vec2::new(100., -100.),
+            (250., 475., -FRAC_PI_4, None),
             Color::PINK,
             30.,
-            vec![HydrogenGasVents],
+            2.,
+            0.2,
+            Demand::default().with(Food, DemandCurve::new(FOOD_PER_THOUSAND, 5, 0.5, 9)),
+            vec![Deposit::new(HydrogenTanks, 20000., 0.)],
+            vec![("Gas vents", gas_vents, 1)],
             false,
         ),
         (
             "Forge",
-            Vec2::new(-100., 100.),
+            (200., 340., 3. * FRAC_PI_4, None),
             Color::GRAY,
             15.,
+            4.,
+            0.2,
+            Demand::default().with(Food, DemandCurve::new(FOOD_PER_THOUSAND, 5, 0.5, 9)),
             vec![],
-            true,
+            vec![("Hydrogen refinery", refinery, 1)],
+            false,
         ),
     ];
-    for (name, position, color, radius, natural_resources, hydrogen_refinery) in planets {
+    let mut spawned = HashMap::new();
+    for (
+        name,
+        (orbit_radius, period, angle, around),
+        color,
+        radius,
+        population,
+        spread,
+        demand,
+        deposits,
+        facilities,
+        order_book,
+    ) in planets
+    {
+        let around = around.map(|parent| *spawned.get(parent).expect("Moons come after planets"));
         let shape = shapes::Circle {
             radius,
             center: Vec2::default(),
@@ -103,18 +264,37 @@ fn planet_setup(mut commands: Commands, fonts: Res<Fonts>) {
         //     },
         //     Transform::default(),
         // ));
-        if hydrogen_refinery {
-            planet.insert(HydrogenRefinery);
+        if order_book {
+            planet.insert(OrderBook::default());
         }
+        let mut store = Store::default().with_spread(spread);
+        ledger.mint(&mut store.wallet, 1000);
+        // the population's savings, the food they bought goes in their pantry
+        let mut wallet = Wallet::default();
+        ledger.mint(&mut wallet, 100);
+        let orbit = Orbit {
+            around,
+            radius: orbit_radius,
+            period,
+            angle,
+        };
+        spawned.insert(name, planet.id());
         planet
             .insert(Planet)
-            .insert(Transform::from_translation(position.extend(0.)))
+            .insert(orbit)
             .insert(Name(name.to_string()))
             .insert(Selectable::default())
-            .insert(PlanetaryResources {
-                resources: natural_resources,
-            })
-            .insert(Store::default())
+            .insert(store)
+            .insert(wallet)
+            .insert(Population::new(population, POPULATION_GROWTH))
+            .insert(demand)
+            .insert(Deposits::new(deposits))
+            .insert(Inventory::with_capacity(PANTRY_SIZE))
+            // bigger planets have room for more ships
+            .insert(DockingPort::new(
+                (radius / 10.).ceil() as usize,
+                radius + 10.,
+            ))
             .with_children(|parent| {
                 parent.spawn(Text2dBundle {
                     text: Text::from_section(
@@ -130,72 +310,457 @@ fn planet_setup(mut commands: Commands, fonts: Res<Fonts>) {
                     ..Default::default()
                 });
             });
+        let planet = planet.id();
+        for (facility_name, recipe, throughput) in facilities {
+            spawn_facility(
+                &mut commands,
+                &mut ledger,
+                planet,
+                format!("{} {}", name, facility_name),
+                recipe,
+                throughput,
+            );
+        }
+    }
+}
+
+fn spawn_facility(
+    commands: &mut Commands,
+    ledger: &mut Ledger,
+    planet: Entity,
+    name: String,
+    recipe: Recipe,
+    throughput: Amount,
+) {
+    let mut wallet = Wallet::default();
+    ledger.mint(&mut wallet, FACILITY_CAPITAL);
+    commands.spawn((
+        Facility::new(planet, recipe, throughput),
+        Name(name),
+        Inventory::with_capacity(FACILITY_STORAGE),
+        wallet,
+    ));
+}
+
+fn advance_orbits(time: Res<Time>, mut orbits: Query<&mut Orbit>) {
+    for mut orbit in orbits.iter_mut() {
+        orbit.advance(time.delta_seconds());
     }
 }
 
-// right now assumes 1 store per planet
-fn population_buys_food(
+fn place_orbiting_bodies(
+    orbits: Query<&Orbit>,
+    mut bodies: Query<(Entity, &mut Transform), With<Orbit>>,
+) {
+    for (body, mut transform) in bodies.iter_mut() {
+        if let Some(position) = position_in(body, 0., &orbits) {
+            transform.translation = position.extend(transform.translation.z);
+        }
+    }
+}
+
+/// Where an orbiting body will be `seconds` from now, `None` if it doesn't orbit anything
+pub(crate) fn position_in(body: Entity, seconds: f32, orbits: &Query<&Orbit>) -> Option<Vec2> {
+    let orbit = orbits.get(body).ok()?;
+    let center = match orbit.around {
+        Some(parent) => position_in(parent, seconds, orbits)?,
+        None => Vec2::ZERO,
+    };
+    Some(center + orbit.offset_in(seconds))
+}
+
+/// People buy what they want at the store's prices, more of it when it's cheap. Everything but
+/// food is used up as soon as it's bought, food goes in the pantry
+#[allow(clippy::type_complexity)]
+fn population_consumes(
     time: Res<Time>,
     mut once_per_second: Local<OncePerSecond>,
-    mut stores: Query<&mut Store>,
+    mut planets: Query<
+        (
+            Entity,
+            &Name,
+            &Population,
+            &mut Demand,
+            &mut Store,
+            &mut Wallet,
+            &mut Inventory,
+            Option<&mut OrderBook>,
+        ),
+        With<Planet>,
+    >,
+    mut ledger: ResMut<Ledger>,
 ) {
-    if once_per_second.timer.tick(time.delta()).just_finished() {
-        for mut store in stores.iter_mut() {
-            match store.price_check_buy_specific_from_store(Commodity::Food) {
-                Some(price) if price.price < 6 => {
-                    // affordable food
-                    let receipt = store
-                        .buy_from_store(Commodity::Food, 1, Some(price.price))
-                        .expect("We just checked, this should work");
-                    debug!("People bought food: {:?}", receipt);
+    if once_per_second
+        .timer
+        .tick(time.delta())
+        .just_finished()
+        .not()
+    {
+        return;
+    }
+    for (planet, name, population, mut demand, mut store, mut wallet, mut pantry, mut order_book) in
+        planets.iter_mut()
+    {
+        for commodity in demand.commodities() {
+            if commodity != Commodity::Food {
+                let used = pantry.get(&commodity);
+                if used > 0 {
+                    pantry.take(&commodity, used);
+                }
+            }
+            let Some(listing) = store.price_check_buy_specific_from_store(commodity) else {
+                debug!("No {} for sale on {}", commodity, name.0);
+                continue;
+            };
+            demand.accrue(commodity, listing.price, population.size(), 1.);
+            // dear food means fewer treats, not going hungry
+            let need = if commodity == Commodity::Food && demand.will_pay(commodity, listing.price)
+            {
+                population
+                    .food_wanted(1.)
+                    .saturating_sub(pantry.get(&Commodity::Food))
+            } else {
+                0
+            };
+            let wanted = demand
+                .wanted(commodity)
+                .max(need)
+                .min(pantry.room_for(commodity));
+            if wanted == 0 {
+                continue;
+            }
+            match order_book.as_mut() {
+                Some(order_book) => {
+                    // let traders compete with the store for the money, the order book delivers
+                    // to the pantry
+                    let affordable = wanted.min(wallet.credits() / listing.price.max(1));
+                    let ordered = order_book
+                        .orders()
+                        .iter()
+                        .any(|order| order.trader == planet && order.commodity == commodity);
+                    if affordable == 0 || ordered {
+                        continue;
+                    }
+                    // demand is only met once the order fills, clearing the book counts it then
+                    if let Err(e) = order_book.post_buy(
+                        planet,
+                        commodity,
+                        affordable,
+                        listing.price,
+                        time.elapsed_seconds() + ORDER_TTL,
+                        &mut wallet,
+                        &mut ledger,
+                    ) {
+                        debug!("People couldn't order {}: {:?}", commodity, e);
+                    }
                 }
-                _ => {
-                    // no affordable food :o
-                    todo!("No affordable food");
+                None => {
+                    if let Some(receipt) = store.buy_from_store(
+                        commodity,
+                        wanted,
+                        Some(listing.price),
+                        OrderMode::Partial,
+                        &mut wallet,
+                        &mut ledger,
+                    ) {
+                        debug!("People bought {:?}", receipt);
+                        demand.bought(commodity, receipt.amount);
+                        if let Err(e) = pantry.add(commodity, receipt.amount) {
+                            warn!("No room in the pantry: {:?}", e);
+                        }
+                    }
                 }
             }
         }
     }
 }
 
-fn produce_commodities_from_natural_resources(
+/// People earn their income and eat from their pantry. Hungry people leave for the planet with the
+/// least unrest
+#[allow(clippy::type_complexity)]
+fn population_lives(
     time: Res<Time>,
     mut once_per_second: Local<OncePerSecond>,
-    mut stores: Query<(&mut Store, &PlanetaryResources)>,
+    mut planets: Query<(Entity, &Name, &mut Population, &mut Wallet, &mut Inventory), With<Planet>>,
+    mut ledger: ResMut<Ledger>,
 ) {
-    if once_per_second.timer.tick(time.delta()).just_finished() {
-        for (mut store, natural_resources) in stores.iter_mut() {
-            for resource in &natural_resources.resources {
-                match resource {
-                    FertileSoil => {
-                        store.give(Commodity::Food, 10);
-                    }
-                    HydrogenGasVents => {
-                        store.give(Commodity::HydrogenTanks, 20);
-                    }
-                }
+    if once_per_second
+        .timer
+        .tick(time.delta())
+        .just_finished()
+        .not()
+    {
+        return;
+    }
+    let mut emigrants = vec![];
+    for (planet, _, mut population, mut wallet, mut pantry) in planets.iter_mut() {
+        let income = (population.size() * INCOME_PER_THOUSAND).round() as Credits;
+        ledger.mint(&mut wallet, income);
+
+        let left = population.eat_from(&mut pantry, 1.);
+        if left > 0. {
+            emigrants.push((planet, left));
+        }
+    }
+
+    for (from, thousands) in emigrants {
+        let Some(to) = planets
+            .iter()
+            .filter(|(planet, ..)| *planet != from)
+            .min_by(|(_, _, a, ..), (_, _, b, ..)| a.unrest().total_cmp(&b.unrest()))
+            .map(|(planet, ..)| planet)
+        else {
+            continue;
+        };
+        let [(_, from_name, ..), (_, to_name, mut population, ..)] = planets
+            .get_many_mut([from, to])
+            .expect("Both planets were just there");
+        debug!(
+            "{:.2} thousand people left {} for {}",
+            thousands, from_name.0, to_name.0
+        );
+        population.welcome(thousands);
+    }
+}
+
+/// Facilities work off their own stock, or the planet's deposits, and pay the planet's population
+/// for the work
+#[allow(clippy::type_complexity)]
+fn run_facilities(
+    time: Res<Time>,
+    mut facilities: Query<(&Name, &mut Facility, &mut Inventory, &mut Wallet)>,
+    mut planets: Query<(&mut Wallet, &mut Deposits), (With<Planet>, Without<Facility>)>,
+    mut ledger: ResMut<Ledger>,
+) {
+    for (name, mut facility, mut inventory, mut wallet) in facilities.iter_mut() {
+        let Ok((mut workers, mut deposits)) = planets.get_mut(facility.planet) else {
+            continue;
+        };
+        // nobody works for free
+        if wallet
+            .can_afford(facility.recipe.wages * facility.throughput)
+            .not()
+        {
+            continue;
+        }
+        let batches = facility.run(time.delta_seconds(), &mut inventory, &mut deposits);
+        if batches > 0 {
+            debug!("{} made {} batches", name.0, batches);
+            ledger
+                .transfer(&mut wallet, &mut workers, facility.recipe.wages * batches)
+                .expect("Checked the facility can pay its workers");
+        }
+    }
+}
+
+/// Facilities sell what they made to the store and, as long as that makes them money, buy what
+/// they need for the next few cycles. Unprofitable facilities shut down until prices pick up
+#[allow(clippy::type_complexity)]
+fn facilities_trade(
+    time: Res<Time>,
+    mut once_per_second: Local<OncePerSecond>,
+    mut facilities: Query<(&Name, &mut Facility, &mut Inventory, &mut Wallet)>,
+    mut stores: Query<(&mut Store, &Deposits)>,
+    mut ledger: ResMut<Ledger>,
+) {
+    if once_per_second
+        .timer
+        .tick(time.delta())
+        .just_finished()
+        .not()
+    {
+        return;
+    }
+    for (name, mut facility, mut inventory, mut wallet) in facilities.iter_mut() {
+        let Ok((mut store, deposits)) = stores.get_mut(facility.planet) else {
+            continue;
+        };
+        for (commodity, _) in &facility.recipe.outputs {
+            let amount = inventory.get(commodity);
+            if amount == 0 {
+                continue;
+            }
+            if let Some(receipt) = store.sell_to_store(
+                *commodity,
+                amount,
+                None,
+                OrderMode::Partial,
+                &mut wallet,
+                &mut ledger,
+            ) {
+                inventory.take(commodity, receipt.amount);
+            }
+        }
+
+        let margin = facility.margin(
+            |commodity| {
+                store
+                    .price_check_buy_specific_from_store(commodity)
+                    .map(|listing| listing.price)
+            },
+            |commodity| {
+                store
+                    .price_check_sell_specific_to_store(commodity)
+                    .map(|listing| listing.price)
+            },
+            deposits,
+        );
+        let profitable = margin.is_some_and(|margin| margin > 0);
+        if profitable != facility.running {
+            if profitable {
+                info!("{} is back in business", name.0);
+            } else {
+                info!("{} shuts down, it doesn't pay", name.0);
+            }
+            facility.running = profitable;
+        }
+        if facility.running.not() {
+            continue;
+        }
+
+        for (commodity, amount) in facility.recipe.inputs.clone() {
+            let wanted = (amount * facility.throughput * INPUT_CYCLES)
+                .saturating_sub(inventory.get(&commodity))
+                .min(inventory.room_for(commodity));
+            if wanted == 0 {
+                continue;
+            }
+            if let Some(receipt) = store.buy_from_store(
+                commodity,
+                wanted,
+                None,
+                OrderMode::Partial,
+                &mut wallet,
+                &mut ledger,
+            ) {
+                inventory
+                    .add(commodity, receipt.amount)
+                    .expect("Only bought what fits");
             }
         }
     }
 }
 
-fn hydrogen_refinery_produces_fuel(
+/// Soil recovers while nobody farms it
+fn regenerate_deposits(
     time: Res<Time>,
     mut once_per_second: Local<OncePerSecond>,
-    mut stores: Query<&mut Store, With<HydrogenRefinery>>,
+    mut planets: Query<&mut Deposits>,
 ) {
     if once_per_second.timer.tick(time.delta()).just_finished() {
-        for mut store in stores.iter_mut() {
-            if store.inventory.get(&HydrogenTanks) > 0 {
-                store.take(HydrogenTanks, 1);
-                store.give(Fuel, 1);
-            }
+        for mut deposits in planets.iter_mut() {
+            deposits.regenerate(1.);
+        }
+    }
+}
+
+/// Every now and then prospectors find a new deposit on a planet. If nobody there works that kind
+/// of deposit yet, a facility opens up to do it
+fn discover_deposits(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut once_per_second: Local<OncePerSecond>,
+    mut prospects: ResMut<Prospects>,
+    mut planets: Query<(Entity, &Name, &mut Deposits), With<Planet>>,
+    facilities: Query<&Facility>,
+    mut ledger: ResMut<Ledger>,
+) {
+    if once_per_second
+        .timer
+        .tick(time.delta())
+        .just_finished()
+        .not()
+    {
+        return;
+    }
+    for (planet, name, mut deposits) in planets.iter_mut() {
+        let Some((prospect, size)) = prospects.search() else {
+            continue;
+        };
+        info!(
+            "Prospectors found {:.0} {} on {}",
+            size, prospect.commodity, name.0
+        );
+        deposits.discover(Deposit::new(
+            prospect.commodity,
+            size,
+            prospect.regeneration,
+        ));
+
+        let worked = facilities.iter().any(|facility| {
+            facility.planet == planet
+                && facility.recipe.from_deposits
+                && facility
+                    .recipe
+                    .outputs
+                    .iter()
+                    .any(|(commodity, _)| *commodity == prospect.commodity)
+        });
+        if worked.not() {
+            spawn_facility(
+                &mut commands,
+                &mut ledger,
+                planet,
+                format!("{} {}", name.0, prospect.facility),
+                prospect.recipe.clone(),
+                1,
+            );
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    fn prospects(seed: u64) -> Prospects {
+        Prospects {
+            prospects: vec![Prospect {
+                facility: "Mine",
+                commodity: HydrogenTanks,
+                size: 100.,
+                regeneration: 0.,
+                recipe: Recipe {
+                    inputs: vec![],
+                    outputs: vec![(HydrogenTanks, 1)],
+                    cycle_time: 1.,
+                    wages: 1,
+                    from_deposits: true,
+                },
+            }],
+            rng: fastrand::Rng::with_seed(seed),
+        }
+    }
+
+    #[test]
+    fn prospectors_find_something_every_now_and_then() {
+        let mut prospects = prospects(25);
+
+        let mut finds = 0;
+        for _ in 0..100_000 {
+            if let Some((prospect, size)) = prospects.search() {
+                assert_eq!(prospect.commodity, HydrogenTanks);
+                assert!((50. ..=150.).contains(&size), "{}", size);
+                finds += 1;
+            }
+        }
+
+        // 200 expected
+        assert!((100..300).contains(&finds), "{}", finds);
+    }
+
+    #[test]
+    fn nothing_to_find_without_prospects() {
+        let mut prospects = Prospects {
+            prospects: vec![],
+            ..prospects(25)
+        };
+
+        for _ in 0..10_000 {
+            assert!(prospects.search().is_none());
+        }
+    }
+
     // #[test]
     // fn test_produce_food() {
     //     let mut world = World::default();
@@ -1,37 +1,159 @@
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::{FRAC_PI_2, TAU};
 use std::ops::Not;
 
 use bevy::prelude::*;
 use bevy_prototype_lyon::prelude::*;
-use strum::IntoEnumIterator;
 
 use crate::asset_loading::Fonts;
 use crate::common_components::Name;
+use crate::economy::ORDER_TTL;
+use crate::orders::{Order, OrderIssued};
 use crate::pause::AppState;
-use crate::planet::Planet;
+use crate::planet::{position_in, Planet};
 use crate::unit_selection::Selectable;
 use crate::v2::commodity::Commodity;
+use crate::v2::docking::DockingPort;
+use crate::v2::fuel::{fuel_needed, FuelTank};
 use crate::v2::inventory::{Amount, Inventory};
-use crate::v2::store::{Store, StoreListing};
+use crate::v2::ledger::{Ledger, Wallet};
+use crate::v2::market::Market;
+use crate::v2::movement::{Handling, Movement};
+use crate::v2::orbit::{intercept, Orbit};
+use crate::v2::order_book::{OrderBook, Side};
+use crate::v2::planner::{plan_itinerary, travel_time, Constraints, ShipProfile};
+use crate::v2::store::{Credits, OrderMode, Receipt, ReservationId, Store};
 
 pub struct ShipPlugin;
 
 impl Plugin for ShipPlugin {
     fn build(&self, app: &mut App) {
+        app.add_event::<ActionOutcome>();
         app.add_systems(Startup, ship_setup);
         app.add_systems(
             Update,
             (
+                follow_orders,
                 ship_decision_system,
+                docking_system,
                 move_ship_towards_objective,
+                carry_out_ship_actions,
                 trade_with_planet,
+                dispatch_rescuers,
+                rescue_stranded_ships,
+                point_hulls_along_heading,
+                log_action_outcomes,
             )
                 .run_if(in_state(AppState::GameRunning)),
         );
     }
 }
 
+/// How many trades ahead a ship plans, each one buying where the previous one sold
+const MAX_TRADE_LEGS: usize = 3;
+/// Extra seconds on top of twice the expected flight time before a reservation lapses
+const RESERVATION_GRACE: f32 = 10.;
+/// How far below the planned price a ship still goes through with a sale
+const PRICE_TOLERANCE: f32 = 0.25;
+/// Seconds a ship waits for a buyer's price to recover before settling for less
+const PRICE_PATIENCE: f32 = 5.;
+
+#[derive(Component)]
+pub(crate) struct Ship;
+
+/// How a ship picks its trades, the player's orders take over from the autopilot until they're
+/// carried out
+#[derive(Debug, Default, Clone, Component)]
+pub(crate) enum Automation {
+    /// Whatever pays best
+    #[default]
+    Automatic,
+    /// Flies the same trades over and over, whether they pay or not
+    RepeatRoute(Vec<RouteLeg>),
+    TradeOnly(Commodity),
+    /// Only trades with planets this close to its home
+    StayNearHome {
+        range: f32,
+    },
+    /// Never plans anything, only follows orders
+    Manual,
+    /// Carrying out the player's orders, then back to the standing orders
+    Orders {
+        then: Box<Automation>,
+    },
+}
+
+impl Automation {
+    /// Whether the ship is left to plan its own trades
+    pub(crate) fn on_autopilot(&self) -> bool {
+        matches!(self, Self::Manual | Self::Orders { .. }).not()
+    }
+
+    /// Puts the player in charge until their orders are carried out, true if that took over from
+    /// the autopilot
+    fn take_orders(&mut self) -> bool {
+        if self.on_autopilot().not() {
+            return false;
+        }
+        let standing = std::mem::take(self);
+        *self = Self::Orders {
+            then: Box::new(standing),
+        };
+        true
+    }
+
+    /// Drops the player's orders and goes back to the standing orders
+    fn resume(&mut self) {
+        if let Self::Orders { then } = self {
+            *self = std::mem::take(then.as_mut());
+        }
+    }
+
+    /// What the standing orders let the ship trade and where, `home` is where its home planet is
+    fn constraints(&self, home: Option<Vec2>) -> Constraints {
+        match self {
+            Self::TradeOnly(commodity) => Constraints {
+                commodity: Some(*commodity),
+                ..default()
+            },
+            Self::StayNearHome { range } => Constraints {
+                within: home.map(|home| (home, *range)),
+                ..default()
+            },
+            Self::Orders { then } => then.constraints(home),
+            Self::Automatic | Self::RepeatRoute(_) | Self::Manual => Constraints::default(),
+        }
+    }
+
+    /// New standing orders, they wait for the player's orders to be carried out first
+    fn stand(&mut self, standing: Automation) {
+        match self {
+            Self::Orders { then } => **then = standing,
+            _ => *self = standing,
+        }
+    }
+}
+
+/// Buy as much as fits at one planet and sell it all at another
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RouteLeg {
+    pub(crate) buy_at: Entity,
+    pub(crate) sell_at: Entity,
+    pub(crate) commodity: Commodity,
+}
+
+/// How far from home a ship told to stay near home goes
+const HOME_RANGE: f32 = 250.;
+
+/// Ran out of fuel mid-flight and can't move until someone brings more
+#[derive(Component)]
+struct Stranded;
+
+/// The planet a ship belongs to
 #[derive(Component)]
-struct Ship;
+pub(crate) struct Home {
+    pub(crate) planet: Entity,
+}
 
 #[derive(Default, Component)]
 struct ActionQueue {
@@ -40,45 +162,405 @@ struct ActionQueue {
 
 #[derive(Component)]
 struct Engine {
+    /// Top speed
     speed: f32,
+    thrust: f32,
+    /// Mass of the ship without any cargo
+    hull_mass: f32,
+    /// Radians per second
+    turn_rate: f32,
 }
 
-#[derive(Debug)]
+impl Engine {
+    fn acceleration(&self, cargo_mass: f32) -> f32 {
+        self.thrust / (self.hull_mass + cargo_mass)
+    }
+
+    fn handling(&self, cargo_mass: f32) -> Handling {
+        Handling {
+            max_speed: self.speed,
+            max_acceleration: self.acceleration(cargo_mass),
+            turn_rate: self.turn_rate,
+        }
+    }
+}
+
+/// The ship's triangle, a child so it can turn with the heading without spinning the label
+#[derive(Component)]
+struct Hull;
+
+/// Where an action takes the ship
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target {
+    Point(Vec2),
+    /// Follows the entity if it moves, ships can't be targeted
+    Entity(Entity),
+}
+
+impl Target {
+    fn position(&self, places: &Query<&Transform, Without<Ship>>) -> Option<Vec2> {
+        match self {
+            Self::Point(point) => Some(*point),
+            Self::Entity(entity) => places
+                .get(*entity)
+                .ok()
+                .map(|transform| transform.translation.truncate()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WaitFor {
+    /// Seconds left to wait
+    Duration(f32),
+    /// Until the store has at least `amount` of the commodity for sale
+    Stock {
+        store: Entity,
+        commodity: Commodity,
+        amount: Amount,
+    },
+}
+
+#[derive(Debug, Clone)]
 enum ShipAction {
     Buy {
         planet_to_buy_at: Entity,
         store: Entity,
         commodity: Commodity,
+        amount: Amount,
+        reservation: ReservationId,
     },
     Sell {
         planet_to_sell_at: Entity,
         store: Entity,
         commodity: Commodity,
+        /// Lowest unit price the ship accepts, anything less is a failed sale
+        min_price: Credits,
+        reservation: ReservationId,
+    },
+    MoveTo(Target),
+    /// Holds position, wherever the ship is
+    Wait(WaitFor),
+    /// Parks at the planet until there's something else to do
+    Dock {
+        planet: Entity,
+    },
+    Undock,
+    /// Buys fuel for the ship's own use
+    Refuel {
+        planet: Entity,
+        store: Entity,
+        amount: Amount,
+    },
+    /// Visits the waypoints in order, over and over, until the queue is cleared. See
+    /// `ShipAction::patrol`
+    Patrol {
+        waypoints: Vec<Target>,
+        next: usize,
+    },
+}
+
+impl ShipAction {
+    /// `None` without any waypoints, there'd be nowhere to go and no end to it
+    fn patrol(waypoints: Vec<Target>) -> Option<Self> {
+        waypoints
+            .is_empty()
+            .not()
+            .then_some(Self::Patrol { waypoints, next: 0 })
+    }
+
+    /// Where the ship has to be to carry out the action, `None` if it can do it anywhere
+    fn destination(&self) -> Option<Target> {
+        match self {
+            Self::Buy {
+                planet_to_buy_at: planet,
+                ..
+            }
+            | Self::Sell {
+                planet_to_sell_at: planet,
+                ..
+            }
+            | Self::Dock { planet }
+            | Self::Refuel { planet, .. } => Some(Target::Entity(*planet)),
+            Self::MoveTo(target) => Some(*target),
+            Self::Patrol { waypoints, next } => waypoints.get(*next).copied(),
+            Self::Wait(_) | Self::Undock => None,
+        }
+    }
+
+    /// The store the ship trades with
+    const fn store(&self) -> Option<Entity> {
+        match self {
+            Self::Buy { store, .. } | Self::Sell { store, .. } | Self::Refuel { store, .. } => {
+                Some(*store)
+            }
+            _ => None,
+        }
+    }
+
+    /// The planet whose docking port the action needs
+    const fn port(&self) -> Option<Entity> {
+        match self {
+            Self::Buy {
+                planet_to_buy_at: planet,
+                ..
+            }
+            | Self::Sell {
+                planet_to_sell_at: planet,
+                ..
+            }
+            | Self::Dock { planet }
+            | Self::Refuel { planet, .. } => Some(*planet),
+            _ => None,
+        }
+    }
+
+    /// Carries on with the action for `seconds`, returns whether it's done. `arrived` is whether
+    /// the ship made it to the destination, `in_stock` whether a store has an amount for sale
+    fn advance(
+        &mut self,
+        seconds: f32,
+        arrived: bool,
+        docking: Option<&Docking>,
+        more_queued: bool,
+        in_stock: impl Fn(Entity, Commodity, Amount) -> bool,
+    ) -> bool {
+        match self {
+            Self::MoveTo(_) => arrived,
+            Self::Wait(WaitFor::Duration(seconds_left)) => {
+                *seconds_left -= seconds;
+                *seconds_left <= 0.
+            }
+            Self::Wait(WaitFor::Stock {
+                store,
+                commodity,
+                amount,
+            }) => in_stock(*store, *commodity, *amount),
+            // stays parked until there's something else to do
+            Self::Dock { planet } => {
+                more_queued
+                    && matches!(
+                        docking,
+                        Some(Docking::Docked { planet: docked_at, .. }) if docked_at == planet
+                    )
+            }
+            // the docking system clears the slot
+            Self::Undock => docking.is_none(),
+            Self::Patrol { waypoints, next } if arrived => {
+                *next = (*next + 1) % waypoints.len();
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Flying out to a stranded ship at `position`, they can't be targeted so it's a point
+    fn is_rescue(&self, position: Vec2) -> bool {
+        matches!(self, Self::MoveTo(Target::Point(point)) if *point == position)
+    }
+
+    const fn reservation(&self) -> Option<ReservationId> {
+        match self {
+            Self::Buy { reservation, .. } | Self::Sell { reservation, .. } => Some(*reservation),
+            _ => None,
+        }
+    }
+}
+
+impl ActionQueue {
+    /// Where the current action takes the ship, `None` if it should stay where it is
+    fn destination(&self, places: &Query<&Transform, Without<Ship>>) -> Option<Vec2> {
+        self.queue.first()?.destination()?.position(places)
+    }
+
+    /// Whether the ship is on its way to a stranded ship at `position`
+    fn is_rescuing(&self, position: Vec2) -> bool {
+        self.queue.iter().any(|action| action.is_rescue(position))
+    }
+}
+
+/// How far out from a planet's docking slots a ship asks for one, and counts as having arrived
+const APPROACH_DISTANCE: f32 = 40.;
+/// Close enough to a point, or to a docking slot, to count as being on it
+const DOCKING_TOLERANCE: f32 = 5.;
+/// Seconds it takes to clear a docking slot
+const UNDOCK_SECONDS: f32 = 1.;
+/// Seconds spent loading or unloading each unit of cargo
+const LOAD_SECONDS_PER_UNIT: f32 = 0.1;
+/// How close a stranded ship has to drift to a planet or another ship to be helped
+const RESCUE_RANGE: f32 = 50.;
+
+/// Where a ship is in getting on and off a planet's docking port
+#[derive(Debug, Clone, Copy, Component)]
+enum Docking {
+    /// Flying in to its slot, or holding off the planet until one frees up
+    Approaching { planet: Entity },
+    Docked {
+        planet: Entity,
+        /// Seconds of loading or unloading left before the ship can trade again, or leave
+        busy_for: f32,
+    },
+    /// Clearing the slot, it's free for the next ship once this is done
+    Undocking { planet: Entity, seconds_left: f32 },
+}
+
+impl Docking {
+    const fn planet(&self) -> Entity {
+        match *self {
+            Self::Approaching { planet }
+            | Self::Docked { planet, .. }
+            | Self::Undocking { planet, .. } => planet,
+        }
+    }
+
+    /// Where the ship stands after `seconds` more of carrying out `action`, `None` once it's off
+    /// the port. `in_slot` is whether an approaching ship has made it onto its slot
+    fn step(self, action: Option<&ShipAction>, in_slot: bool, seconds: f32) -> Option<Self> {
+        match self {
+            Self::Approaching { planet } => {
+                if action.and_then(ShipAction::port) != Some(planet) {
+                    return None;
+                }
+                Some(if in_slot {
+                    Self::Docked {
+                        planet,
+                        busy_for: 0.,
+                    }
+                } else {
+                    self
+                })
+            }
+            Self::Docked { planet, busy_for } => {
+                let busy_for = busy_for - seconds;
+                // waiting around is fine, anything elsewhere or nothing at all frees the slot
+                let leaving = match action {
+                    Some(ShipAction::Undock) | None => true,
+                    Some(action) => action
+                        .destination()
+                        .is_some_and(|destination| destination != Target::Entity(planet)),
+                };
+                Some(if leaving && busy_for <= 0. {
+                    Self::Undocking {
+                        planet,
+                        seconds_left: UNDOCK_SECONDS,
+                    }
+                } else {
+                    Self::Docked { planet, busy_for }
+                })
+            }
+            Self::Undocking {
+                planet,
+                seconds_left,
+            } => {
+                let seconds_left = seconds_left - seconds;
+                (seconds_left > 0.).then_some(Self::Undocking {
+                    planet,
+                    seconds_left,
+                })
+            }
+        }
+    }
+}
+
+/// Why a ship couldn't carry out its current action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ActionFailure {
+    OutOfStock,
+    CantAfford,
+    HoldFull,
+    TankFull,
+    NothingToSell,
+    /// The store is full or broke
+    NoBuyer,
+    PriceTooLow,
+}
+
+/// What a ship does about a failed action
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Recovery {
+    /// Stay docked and try again later
+    Wait {
+        until: f32,
+    },
+    RetryLower {
+        min_price: Credits,
+    },
+    /// Take the cargo to another buyer instead
+    Reroute {
+        planet: Entity,
+        min_price: Credits,
+    },
+    /// Give up on the sale and keep the cargo in the hold
+    StoreCargo,
+    /// Give up on the action and anything that depended on it, the ship comes up with a new plan
+    /// once it's done with the rest
+    Replan,
+    /// Nothing to do, move on to the next action
+    Skip,
+}
+
+#[derive(Debug)]
+enum Outcome {
+    Traded(Receipt),
+    /// A limit order was filled or expired, see the order book for what came of it
+    OrderSettled,
+    Failed {
+        reason: ActionFailure,
+        recovery: Recovery,
     },
 }
 
-fn ship_setup(mut commands: Commands, fonts: Res<Fonts>) {
-    for (ship_name, speed, capacity) in vec![("Wayfarer", 300., 5), ("Envoy", 100., 20)] {
+/// Sent whenever a ship finishes or fails a trade
+#[derive(Event, Debug)]
+struct ActionOutcome {
+    ship: Entity,
+    planet: Entity,
+    outcome: Outcome,
+}
+
+fn ship_setup(mut commands: Commands, fonts: Res<Fonts>, mut ledger: ResMut<Ledger>) {
+    for (ship_name, speed, thrust, hull_mass, turn_rate, capacity, tank) in [
+        ("Wayfarer", 300., 1500., 5., TAU, 5, 10.),
+        ("Envoy", 100., 1000., 20., FRAC_PI_2, 20, 30.),
+    ] {
+        let mut wallet = Wallet::default();
+        ledger.mint(&mut wallet, 100);
         commands
-            .spawn((
-                ShapeBundle {
-                    path: GeometryBuilder::build_as(&shapes::Circle {
-                        // todo, triangle instead of circle
-                        radius: 5.,
-                        center: Vec2::default(),
-                    }),
-                    ..Default::default()
-                },
-                Fill::color(Color::GOLD),
-                Stroke::new(Color::WHITE, 1.),
-            ))
+            .spawn(SpatialBundle::default())
             .insert(Ship)
             .insert(ActionQueue::default())
+            .insert(Automation::default())
             .insert(Selectable::default())
-            .insert(Engine { speed })
+            .insert(Engine {
+                speed,
+                thrust,
+                hull_mass,
+                turn_rate,
+            })
+            .insert(Movement::default())
             .insert(Name(ship_name.to_string()))
             .insert(Inventory::with_capacity(capacity))
+            .insert(FuelTank::full(tank))
+            .insert(wallet)
             .with_children(|parent| {
+                parent
+                    .spawn((
+                        ShapeBundle {
+                            // pointing along the x-axis, same as a heading of 0
+                            path: GeometryBuilder::build_as(&shapes::Polygon {
+                                points: vec![
+                                    Vec2::new(8., 0.),
+                                    Vec2::new(-5., 5.),
+                                    Vec2::new(-5., -5.),
+                                ],
+                                closed: true,
+                            }),
+                            ..Default::default()
+                        },
+                        Fill::color(Color::GOLD),
+                        Stroke::new(Color::WHITE, 1.),
+                    ))
+                    .insert(Hull);
                 parent.spawn(Text2dBundle {
                     text: Text::from_section(
                         ship_name,
@@ -96,246 +578,1877 @@ fn ship_setup(mut commands: Commands, fonts: Res<Fonts>) {
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn ship_decision_system(
-    mut action_queues: Query<(&mut ActionQueue, &Name)>,
-    stores: Query<(Entity, &Planet, &Store, &Name)>,
+    mut ships: Query<
+        (
+            &mut ActionQueue,
+            &mut Automation,
+            Option<&Home>,
+            &Name,
+            &Transform,
+            &Engine,
+            &Inventory,
+            &Wallet,
+            &FuelTank,
+        ),
+        (With<Ship>, Without<Stranded>),
+    >,
+    planet_names: Query<&Name, With<Planet>>,
+    places: Query<&Transform, Without<Ship>>,
+    mut stores: Query<&mut Store>,
+    orbits: Query<&Orbit>,
+    market: Res<Market>,
+    time: Res<Time>,
 ) {
-    for (mut action_queue, name) in action_queues.iter_mut() {
+    let planet_name = |planet: Entity| {
+        planet_names
+            .get(planet)
+            .map(|name| name.0.clone())
+            .unwrap_or_default()
+    };
+    for (
+        mut action_queue,
+        mut automation,
+        home,
+        name,
+        transform,
+        engine,
+        inventory,
+        wallet,
+        tank,
+    ) in ships.iter_mut()
+    {
         if action_queue.queue.is_empty().not() {
             continue;
         }
+        if let Automation::Orders { .. } = *automation {
+            info!("[{}]: Orders carried out, back on autopilot", name.0);
+            automation.resume();
+        }
 
-        let buy_from_stores_listings = stores
-            .iter()
-            .map(
-                |(entity, planet, store, name): (Entity, &Planet, &Store, &Name)| {
-                    let listings = store.price_check_buy_from_store();
-                    (entity, planet, name, listings)
-                },
-            )
-            .collect::<Vec<_>>();
-
-        let sell_to_stores_listings = stores
-            .iter()
-            .map(
-                |(entity, planet, store, name): (Entity, &Planet, &Store, &Name)| {
-                    let listings = store.price_check_sell_to_store();
-                    (entity, planet, name, listings)
-                },
-            )
-            .collect::<Vec<_>>();
+        let ship = ShipProfile {
+            position: transform.translation.truncate(),
+            speed: engine.speed,
+            thrust: engine.thrust,
+            hull_mass: engine.hull_mass,
+            hold: inventory,
+            budget: wallet.credits(),
+            constraints: Constraints::default(),
+            fuel: tank.fuel(),
+            tank_capacity: tank.capacity,
+            fuel_price: market
+                .get_sellers(Commodity::Fuel)
+                .map(|listing| listing.price)
+                .min()
+                .unwrap_or_default() as f32,
+        };
+        let home_position = home.and_then(|home| Target::Entity(home.planet).position(&places));
+        let constraints = match &*automation {
+            Automation::Manual | Automation::Orders { .. } => continue,
+            Automation::RepeatRoute(route) => {
+                queue_route(
+                    route,
+                    &ship,
+                    &mut action_queue,
+                    &mut stores,
+                    &places,
+                    time.elapsed_seconds(),
+                );
+                if action_queue.queue.is_empty() {
+                    info!("[{}]: Can't fly the route", name.0);
+                    action_queue
+                        .queue
+                        .push(refuel_or_wait(&ship, &market, name, planet_name));
+                }
+                continue;
+            }
+            Automation::StayNearHome { .. } if home_position.is_none() => {
+                warn!("[{}]: Has no home to stay near, waiting for orders", name.0);
+                *automation = Automation::Manual;
+                continue;
+            }
+            Automation::Automatic | Automation::TradeOnly(_) | Automation::StayNearHome { .. } => {
+                automation.constraints(home_position)
+            }
+        };
 
-        if let Some(trade_route) =
-            decide_trade_route(buy_from_stores_listings, sell_to_stores_listings)
-        {
-            action_queue.queue.push(ShipAction::Buy {
-                planet_to_buy_at: trade_route.store_to_buy_from,
-                store: trade_route.store_to_buy_from,
-                commodity: trade_route.commodity,
-            });
+        // cargo left over from a failed sale goes to whoever pays the most for it now, if anyone
+        // has room and money for it. Otherwise it stays in the hold
+        for (&commodity, &amount) in inventory.items.iter().filter(|(_, amount)| **amount > 0) {
+            let Some(listing) = market
+                .get_buyers(commodity)
+                .filter(|listing| listing.amount > 0)
+                .filter(|listing| constraints.allows(listing))
+                .max_by_key(|listing| listing.price)
+            else {
+                continue;
+            };
+            let amount = amount.min(listing.amount);
+            let flight_time = travel_time(
+                transform.translation.truncate().distance(listing.position),
+                engine.speed,
+                engine.acceleration(inventory.mass()),
+            );
+            let reservation = stores
+                .get_mut(listing.planet)
+                .expect("Listed stores exist")
+                .reserve(
+                    Side::Sell,
+                    commodity,
+                    amount,
+                    time.elapsed_seconds() + flight_time * 2. + RESERVATION_GRACE,
+                );
             action_queue.queue.push(ShipAction::Sell {
-                planet_to_sell_at: trade_route.store_to_sell_to,
-                store: trade_route.store_to_sell_to,
-                commodity: trade_route.commodity,
+                planet_to_sell_at: listing.planet,
+                store: listing.planet,
+                commodity,
+                min_price: sale_floor(listing.price),
+                reservation,
             });
+            info!(
+                "[{}]: Selling {} leftover {:?} at {:?}",
+                name.0,
+                amount,
+                commodity,
+                planet_name(listing.planet)
+            );
+        }
+        if action_queue.queue.is_empty().not() {
+            continue;
+        }
+
+        let ship = ShipProfile {
+            constraints,
+            ..ship
+        };
+        let store = |planet: Entity| stores.get(planet).ok();
+        let position_in = |planet: Entity, seconds: f32| position_in(planet, seconds, &orbits);
+        if let Some(itinerary) = plan_itinerary(&market, store, position_in, &ship, MAX_TRADE_LEGS)
+        {
+            // hold the stock and warehouse space so other ships plan around it
+            let mut flight_time = 0.;
+            for leg in &itinerary.legs {
+                flight_time += leg.travel_time;
+                let expires_at = time.elapsed_seconds() + flight_time * 2. + RESERVATION_GRACE;
+                let mut reserve = |planet: Entity, side: Side| {
+                    stores
+                        .get_mut(planet)
+                        .expect("Planned against this store")
+                        .reserve(side, leg.commodity, leg.amount, expires_at)
+                };
+                let buy_reservation = reserve(leg.store_to_buy_from, Side::Buy);
+                let sell_reservation = reserve(leg.store_to_sell_to, Side::Sell);
+                if leg.refuel > 0 {
+                    action_queue.queue.push(ShipAction::Refuel {
+                        planet: leg.store_to_buy_from,
+                        store: leg.store_to_buy_from,
+                        amount: leg.refuel,
+                    });
+                }
+                action_queue.queue.push(ShipAction::Buy {
+                    planet_to_buy_at: leg.store_to_buy_from,
+                    store: leg.store_to_buy_from,
+                    commodity: leg.commodity,
+                    amount: leg.amount,
+                    reservation: buy_reservation,
+                });
+                action_queue.queue.push(ShipAction::Sell {
+                    planet_to_sell_at: leg.store_to_sell_to,
+                    store: leg.store_to_sell_to,
+                    commodity: leg.commodity,
+                    min_price: sale_floor(leg.price_to_sell_commodity / leg.amount),
+                    reservation: sell_reservation,
+                });
 
+                info!(
+                    "[{}]: New trade, buy {} {:?} at {:?} for {}, sell att {:?} for {}",
+                    name.0,
+                    leg.amount,
+                    leg.commodity,
+                    planet_name(leg.store_to_buy_from),
+                    leg.cost_to_buy_commodity,
+                    planet_name(leg.store_to_sell_to),
+                    leg.price_to_sell_commodity,
+                );
+            }
             info!(
-                "[{}]: New trade, buy {:?} at {:?} for {}, sell att {:?} for {}",
+                "[{}]: {} legs, {:.1}/s",
                 name.0,
-                trade_route.commodity,
-                trade_route.store_to_buy_from_name,
-                trade_route.cost_to_buy_commodity,
-                trade_route.store_to_sell_to_name,
-                trade_route.price_to_sell_commodity
+                itinerary.legs.len(),
+                itinerary.profit_per_second()
             );
         } else {
-            info!("No profitable trade possible");
+            info!("[{}]: No profitable trade possible", name.0);
+            action_queue
+                .queue
+                .push(refuel_or_wait(&ship, &market, name, planet_name));
         }
     }
 }
 
-fn move_ship_towards_objective(
-    mut ships: Query<(&mut Transform, &ActionQueue, &Engine), With<Ship>>,
-    planets: Query<(Entity, &Transform), Without<Ship>>,
-    time: Res<Time>,
-) {
-    for (mut ship_transform, action_queue, engine) in ships.iter_mut() {
-        if action_queue.queue.is_empty() {
-            continue;
+/// Tops up at the nearest fuel station when the tank is below half, otherwise waits a bit
+fn refuel_or_wait(
+    ship: &ShipProfile,
+    market: &Market,
+    name: &Name,
+    planet_name: impl Fn(Entity) -> String,
+) -> ShipAction {
+    match nearest_fuel_station(ship, market).filter(|_| ship.fuel < ship.tank_capacity / 2.) {
+        Some(station) => {
+            info!(
+                "[{}]: Running low on fuel, refuelling at {}",
+                name.0,
+                planet_name(station)
+            );
+            ShipAction::Refuel {
+                planet: station,
+                store: station,
+                amount: (ship.tank_capacity - ship.fuel).max(0.) as Amount,
+            }
         }
+        None => ShipAction::Wait(WaitFor::Duration(1.)),
+    }
+}
 
-        let action = action_queue.queue.first().expect("There's a action here");
+/// The closest planet selling fuel that the ship has enough fuel left to reach
+fn nearest_fuel_station(ship: &ShipProfile, market: &Market) -> Option<Entity> {
+    market
+        .get_sellers(Commodity::Fuel)
+        .filter(|listing| {
+            ship.fuel_needed(ship.position, listing.position, ship.hold.mass()) <= ship.fuel
+        })
+        .min_by(|a, b| {
+            ship.position
+                .distance(a.position)
+                .total_cmp(&ship.position.distance(b.position))
+        })
+        .map(|listing| listing.planet)
+}
 
-        let destination_entity = match action {
-            ShipAction::Buy {
-                planet_to_buy_at: seller,
-                ..
-            } => seller,
-            ShipAction::Sell {
-                planet_to_sell_at: buyer,
-                ..
-            } => buyer,
-        };
+/// A leg of a fixed route the ship can pay for and has the fuel to fly
+#[derive(Debug)]
+struct RouteStop {
+    leg: RouteLeg,
+    amount: Amount,
+    /// Fuel to buy at the seller to make it to the buyer and on to the next seller
+    refuel: Amount,
+    min_price: Credits,
+    /// Seconds from now until the ship gets to the seller and to the buyer
+    at_seller: f32,
+    at_buyer: f32,
+}
 
-        let destination_transform = planets
-            .get_component::<Transform>(*destination_entity)
-            .expect("Planet has an entity");
+/// Works out the legs of a fixed route the ship can fly from where it is, stopping at the first
+/// one it can't afford or doesn't have the fuel for
+fn plan_route<'s>(
+    route: &[RouteLeg],
+    ship: &ShipProfile,
+    position_of: impl Fn(Entity) -> Option<Vec2>,
+    store: impl Fn(Entity) -> Option<&'s Store>,
+) -> Vec<RouteStop> {
+    let mut stops = vec![];
+    let mut position = ship.position;
+    let mut fuel = ship.fuel;
+    let mut budget = ship.budget;
+    let mut flight_time = 0.;
+    for (index, leg) in route.iter().enumerate() {
+        let next_leg = route[(index + 1) % route.len()];
+        let (Some(seller), Some(buyer), Some(next_seller)) = (
+            position_of(leg.buy_at),
+            position_of(leg.sell_at),
+            position_of(next_leg.buy_at),
+        ) else {
+            break;
+        };
+        let (Some(seller_store), Some(buyer_store)) = (store(leg.buy_at), store(leg.sell_at))
+        else {
+            break;
+        };
+        let to_seller = ship.fuel_needed(position, seller, ship.hold.mass());
+        if to_seller > fuel {
+            break;
+        }
+        fuel -= to_seller;
 
-        if destination_transform
-            .translation
-            .distance(ship_transform.translation)
-            < 20.
-        {
-            continue;
+        // tops up for the trip to the buyer with a full hold and back to the next seller
+        let room = ship.hold.room_for(leg.commodity);
+        let cargo_mass = ship.hold.mass() + leg.commodity.mass() * room as f32;
+        let to_buyer = ship.fuel_needed(seller, buyer, cargo_mass);
+        let onwards = ship.fuel_needed(buyer, next_seller, ship.hold.mass());
+        let refuel = ((to_buyer + onwards - fuel).max(0.).ceil() as Amount)
+            .min((ship.tank_capacity - fuel).max(0.) as Amount);
+        let refuel = match refuel {
+            0 => None,
+            refuel => seller_store.quote_buy_with_budget(Commodity::Fuel, refuel, budget),
+        };
+        let refuel_amount = refuel.map_or(0, |quote| quote.amount);
+        if fuel + (refuel_amount as f32) < to_buyer {
+            break;
         }
+        budget -= refuel.map_or(0, |quote| quote.total);
 
-        // move towards destination
-        let diff = destination_transform.translation - ship_transform.translation;
-        let diff = diff.normalize();
+        let Some(buy) = seller_store.quote_buy_with_budget(leg.commodity, room, budget) else {
+            break;
+        };
+        let cargo_mass = ship.hold.mass() + leg.commodity.mass() * buy.amount as f32;
+        budget -= buy.total;
+        fuel += refuel_amount as f32 - ship.fuel_needed(seller, buyer, cargo_mass);
 
-        ship_transform.translation += diff * time.delta_seconds() * engine.speed;
+        flight_time += ship.travel_time(position, seller, ship.hold.mass());
+        let at_seller = flight_time;
+        flight_time += ship.travel_time(seller, buyer, cargo_mass);
+        // whatever it pays right now, give or take
+        let min_price = buyer_store
+            .quote_sell(leg.commodity, buy.amount)
+            .map_or(1, |quote| sale_floor(quote.average_price()));
+        stops.push(RouteStop {
+            leg: *leg,
+            amount: buy.amount,
+            refuel: refuel_amount,
+            min_price,
+            at_seller,
+            at_buyer: flight_time,
+        });
+        position = buyer;
     }
+    stops
 }
 
-fn trade_with_planet(
-    mut ships: Query<(&Transform, &mut ActionQueue, &mut Inventory), With<Ship>>,
-    planets: Query<(Entity, &Transform), Without<Ship>>,
-    mut stores: Query<(Entity, &mut Store), Without<Ship>>,
+/// Queues up every leg of a fixed route the ship can manage, reserving stock and space as it goes
+fn queue_route(
+    route: &[RouteLeg],
+    ship: &ShipProfile,
+    action_queue: &mut ActionQueue,
+    stores: &mut Query<&mut Store>,
+    places: &Query<&Transform, Without<Ship>>,
+    now: f32,
 ) {
-    for (ship_transform, mut action_queue, mut inventory) in ships.iter_mut() {
-        if action_queue.queue.is_empty() {
-            continue;
+    let stops = plan_route(
+        route,
+        ship,
+        |planet| Target::Entity(planet).position(places),
+        |planet| stores.get(planet).ok(),
+    );
+    for stop in stops {
+        let leg = stop.leg;
+        let mut reserve = |planet: Entity, side: Side, arrives_in: f32| {
+            stores
+                .get_mut(planet)
+                .expect("Planned against this store")
+                .reserve(
+                    side,
+                    leg.commodity,
+                    stop.amount,
+                    now + arrives_in * 2. + RESERVATION_GRACE,
+                )
+        };
+        let buy_reservation = reserve(leg.buy_at, Side::Buy, stop.at_seller);
+        let sell_reservation = reserve(leg.sell_at, Side::Sell, stop.at_buyer);
+        if stop.refuel > 0 {
+            action_queue.queue.push(ShipAction::Refuel {
+                planet: leg.buy_at,
+                store: leg.buy_at,
+                amount: stop.refuel,
+            });
         }
+        action_queue.queue.push(ShipAction::Buy {
+            planet_to_buy_at: leg.buy_at,
+            store: leg.buy_at,
+            commodity: leg.commodity,
+            amount: stop.amount,
+            reservation: buy_reservation,
+        });
+        action_queue.queue.push(ShipAction::Sell {
+            planet_to_sell_at: leg.sell_at,
+            store: leg.sell_at,
+            commodity: leg.commodity,
+            min_price: stop.min_price,
+            reservation: sell_reservation,
+        });
+    }
+}
 
-        let action = action_queue.queue.first().expect("There's a action here");
+/// Player orders take over from autopilot, the first one throws away whatever autopilot had planned
+#[allow(clippy::type_complexity)]
+fn follow_orders(
+    mut commands: Commands,
+    mut orders: EventReader<OrderIssued>,
+    mut ships: Query<
+        (
+            &mut ActionQueue,
+            &mut Automation,
+            Option<&Home>,
+            &Name,
+            &Transform,
+            &Engine,
+            &Inventory,
+        ),
+        With<Ship>,
+    >,
+    mut stores: Query<&mut Store>,
+    places: Query<&Transform, Without<Ship>>,
+    time: Res<Time>,
+) {
+    for &OrderIssued {
+        ship,
+        planet,
+        order,
+    } in orders.read()
+    {
+        let Ok((mut action_queue, mut automation, home, name, transform, engine, inventory)) =
+            ships.get_mut(ship)
+        else {
+            continue;
+        };
+        info!("[{}]: Ordered to {}", name.0, order);
+        let new_automation = match order {
+            Order::SetHome => {
+                commands.entity(ship).insert(Home { planet });
+                continue;
+            }
+            Order::ResumeAutopilot => {
+                // the player's orders are dropped, autopilot plans from scratch
+                release_reservations(action_queue.queue.drain(..), &mut stores);
+                automation.resume();
+                continue;
+            }
+            Order::Automatic => Some(Automation::Automatic),
+            Order::Manual => Some(Automation::Manual),
+            Order::TradeOnly(commodity) => Some(Automation::TradeOnly(commodity)),
+            Order::StayNearHome if home.is_none() => {
+                warn!("[{}]: Has no home to stay near", name.0);
+                continue;
+            }
+            Order::StayNearHome => Some(Automation::StayNearHome { range: HOME_RANGE }),
+            Order::ShuttleFromHome(commodity) => {
+                let Some(home) = home else {
+                    warn!("[{}]: Has no home to shuttle from", name.0);
+                    continue;
+                };
+                Some(Automation::RepeatRoute(vec![RouteLeg {
+                    buy_at: home.planet,
+                    sell_at: planet,
+                    commodity,
+                }]))
+            }
+            Order::PatrolFromHome if home.is_none() => {
+                warn!("[{}]: Has no home to patrol from", name.0);
+                continue;
+            }
+            Order::MoveHere
+            | Order::Buy(_)
+            | Order::Sell(_)
+            | Order::WaitFor(_)
+            | Order::Dock
+            | Order::Undock
+            | Order::PatrolFromHome => None,
+        };
+        // the new standing orders apply from the next time the ship plans
+        if let Some(new_automation) = new_automation {
+            automation.stand(new_automation);
+            continue;
+        }
+        if automation.take_orders() {
+            release_reservations(action_queue.queue.drain(..), &mut stores);
+        }
 
-        let destination_entity = match action {
-            ShipAction::Buy {
-                planet_to_buy_at: seller,
-                ..
-            } => seller,
-            ShipAction::Sell {
-                planet_to_sell_at: buyer,
-                ..
-            } => buyer,
+        let flight_time = Target::Entity(planet)
+            .position(&places)
+            .map_or(0., |destination| {
+                travel_time(
+                    destination.distance(transform.translation.truncate()),
+                    engine.speed,
+                    engine.acceleration(inventory.mass()),
+                )
+            });
+        let expires_at = time.elapsed_seconds() + flight_time * 2. + RESERVATION_GRACE;
+        let mut reserve = |side: Side, commodity: Commodity, amount: Amount| {
+            stores
+                .get_mut(planet)
+                .map(|mut store| store.reserve(side, commodity, amount, expires_at))
+        };
+        let action = match order {
+            Order::MoveHere => ShipAction::MoveTo(Target::Entity(planet)),
+            Order::Buy(commodity) => {
+                let amount = inventory.room_for(commodity);
+                let Ok(reservation) = reserve(Side::Buy, commodity, amount) else {
+                    continue;
+                };
+                ShipAction::Buy {
+                    planet_to_buy_at: planet,
+                    store: planet,
+                    commodity,
+                    amount,
+                    reservation,
+                }
+            }
+            Order::Sell(commodity) => {
+                let Ok(reservation) = reserve(Side::Sell, commodity, inventory.get(&commodity))
+                else {
+                    continue;
+                };
+                ShipAction::Sell {
+                    planet_to_sell_at: planet,
+                    store: planet,
+                    commodity,
+                    // the player asked for it, so any price goes
+                    min_price: 1,
+                    reservation,
+                }
+            }
+            Order::WaitFor(commodity) => ShipAction::Wait(WaitFor::Stock {
+                store: planet,
+                commodity,
+                amount: inventory.room_for(commodity).max(1),
+            }),
+            Order::Dock => ShipAction::Dock { planet },
+            Order::Undock => ShipAction::Undock,
+            Order::PatrolFromHome => {
+                let home = home.expect("Ships without a home were turned away above");
+                ShipAction::patrol(vec![Target::Entity(home.planet), Target::Entity(planet)])
+                    .expect("Home and here are two waypoints")
+            }
+            _ => unreachable!("Handled above"),
         };
+        action_queue.queue.push(action);
+    }
+}
+
+/// Lets the stores have back what was held for `actions`
+fn release_reservations(
+    actions: impl IntoIterator<Item = ShipAction>,
+    stores: &mut Query<&mut Store>,
+) {
+    for action in actions {
+        if let (Some(store), Some(reservation)) = (action.store(), action.reservation()) {
+            if let Ok(mut store) = stores.get_mut(store) {
+                store.release(reservation);
+            }
+        }
+    }
+}
 
-        let destination_transform = planets
-            .get_component::<Transform>(*destination_entity)
-            .expect("Planet has an entity");
+fn log_action_outcomes(mut outcomes: EventReader<ActionOutcome>, names: Query<&Name>) {
+    let name = |entity: Entity| {
+        names
+            .get(entity)
+            .map(|name| name.0.clone())
+            .unwrap_or_default()
+    };
+    for ActionOutcome {
+        ship,
+        planet,
+        outcome,
+    } in outcomes.read()
+    {
+        match outcome {
+            Outcome::Traded(receipt) => debug!(
+                "[{}]: Traded {} {:?} at {} for {}",
+                name(*ship),
+                receipt.amount,
+                receipt.commodity,
+                name(*planet),
+                receipt.total
+            ),
+            Outcome::OrderSettled => {
+                debug!("[{}]: Order settled at {}", name(*ship), name(*planet))
+            }
+            Outcome::Failed { reason, recovery } => info!(
+                "[{}]: {:?} at {}, {:?}",
+                name(*ship),
+                reason,
+                name(*planet),
+                recovery
+            ),
+        }
+    }
+}
 
-        if destination_transform
-            .translation
-            .distance(ship_transform.translation)
-            > 20.
-        {
+/// Takes ships through approaching a planet, waiting for a slot, docking and undocking again
+#[allow(clippy::type_complexity)]
+fn docking_system(
+    mut commands: Commands,
+    mut ships: Query<
+        (
+            Entity,
+            &Name,
+            &mut Transform,
+            &ActionQueue,
+            &mut Movement,
+            Option<&mut Docking>,
+        ),
+        With<Ship>,
+    >,
+    mut ports: Query<(&Transform, &Name, &mut DockingPort), Without<Ship>>,
+    time: Res<Time>,
+) {
+    for (ship, name, mut ship_transform, action_queue, mut movement, docking) in ships.iter_mut() {
+        let action = action_queue.queue.first();
+        let wanted_port = action.and_then(ShipAction::port);
+        let position = ship_transform.translation.truncate();
+        let Some(mut docking) = docking else {
+            let Some(planet) = wanted_port else {
+                continue;
+            };
+            let Ok((planet_transform, planet_name, mut port)) = ports.get_mut(planet) else {
+                continue;
+            };
+            let distance = planet_transform.translation.truncate().distance(position);
+            if distance < port.radius + APPROACH_DISTANCE {
+                if port.request(ship).is_none() {
+                    info!(
+                        "[{}]: Waiting for a slot at {}, {} ahead",
+                        name.0,
+                        planet_name.0,
+                        port.place_in_line(ship).unwrap_or_default()
+                    );
+                }
+                commands
+                    .entity(ship)
+                    .insert(Docking::Approaching { planet });
+            }
             continue;
-        }
+        };
 
-        // we're at the right planet
-        match action {
-            ShipAction::Buy {
-                store, commodity, ..
-            } => {
-                let amount_wanted = inventory.space_left();
-                let mut store = stores
-                    .get_component_mut::<Store>(*store)
-                    .expect("Should be a store here");
-                // todo maybe buy should handle this
-                let amount_available = store.inventory.get(&commodity);
-                let receipt = store
-                    .buy_from_store(*commodity, amount_wanted.min(amount_available), None)
-                    .expect("should've managed a buy");
-                action_queue.queue.remove(0);
-                inventory.add(receipt.commodity, receipt.amount);
-                debug!("Bought {:?} for {}", receipt.commodity, receipt.price);
+        let planet = docking.planet();
+        let Ok((planet_transform, planet_name, mut port)) = ports.get_mut(planet) else {
+            commands.entity(ship).remove::<Docking>();
+            continue;
+        };
+        let planet_position = planet_transform.translation.truncate();
+        let in_slot = match *docking {
+            Docking::Approaching { .. } if wanted_port == Some(planet) => {
+                port.request(ship).is_some_and(|slot| {
+                    (planet_position + port.slot_offset(slot)).distance(position)
+                        < DOCKING_TOLERANCE
+                })
             }
-            ShipAction::Sell {
-                store, commodity, ..
-            } => {
-                let amount_to_sell = inventory.get(commodity);
-                let mut store = stores
-                    .get_component_mut::<Store>(*store)
-                    .expect("Should be a store here");
-                if let Some(receipt) = store.sell_to_store(*commodity, amount_to_sell, None) {
-                    action_queue.queue.remove(0);
-                    inventory.take(&receipt.commodity, receipt.amount);
-                    debug!("Sold {:?} for {}", receipt.commodity, receipt.price);
-                } else {
-                    info!("Failed to sell {:?}, jettisoning it into space", commodity);
-                    inventory.discard(*commodity);
-                    action_queue.queue.remove(0);
+            Docking::Docked { .. } => {
+                // ride along in the slot as the planet moves
+                if let Some(slot) = port.slot_of(ship) {
+                    let slot_position = planet_position + port.slot_offset(slot);
+                    ship_transform.translation = slot_position.extend(ship_transform.translation.z);
                 }
+                movement.velocity = Vec2::ZERO;
+                true
+            }
+            _ => false,
+        };
+        match docking.step(action, in_slot, time.delta_seconds()) {
+            Some(next) => {
+                if matches!(next, Docking::Docked { .. })
+                    && matches!(*docking, Docking::Approaching { .. })
+                {
+                    info!("[{}]: Docked at {}", name.0, planet_name.0);
+                }
+                *docking = next;
+            }
+            None => {
+                if matches!(*docking, Docking::Undocking { .. }) {
+                    info!("[{}]: Undocked from {}", name.0, planet_name.0);
+                }
+                port.leave(ship);
+                commands.entity(ship).remove::<Docking>();
             }
         }
     }
 }
 
-struct TradeRoute {
-    store_to_buy_from: Entity,
-    store_to_buy_from_name: String,
-    store_to_sell_to: Entity,
-    store_to_sell_to_name: String,
-    commodity: Commodity,
-    cost_to_buy_commodity: Amount,
-    price_to_sell_commodity: Amount,
-}
-
-fn decide_trade_route(
-    buy_from_stores_listings: Vec<(Entity, &Planet, &Name, Vec<StoreListing>)>,
-    sell_to_stores_listings: Vec<(Entity, &Planet, &Name, Vec<StoreListing>)>,
-) -> Option<TradeRoute> {
-    let buy_listings = buy_from_stores_listings
-        .into_iter()
-        .flat_map(|(entity, planet, name, listings)| {
-            listings
-                .into_iter()
-                .map(|listing| (entity, planet, name, listing))
-                .filter(|(_, _, _, listing)| listing.amount > 40) // todo make smarter
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>();
-    let sell_listings = sell_to_stores_listings
-        .into_iter()
-        .flat_map(|(entity, planet, name, listings)| {
-            listings
-                .into_iter()
-                .map(|listing| (entity, planet, name, listing))
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>();
-    // info!("Buy from store listings: {:#?}", buy_listings);
-    // info!("Sell to store listings: {:#?}", sell_listings);
-    let trade_routes = Commodity::iter()
-        .into_iter()
-        .flat_map(|commodity| {
-            let cheapest_buy = buy_listings
-                .iter()
-                .filter(|(_, _, _, listing)| listing.commodity == commodity)
-                .min_by_key(|(_, _, _, listing)| listing.price);
-            let priciest_sell = sell_listings
-                .iter()
-                .filter(|(_, _, _, listing)| listing.commodity == commodity)
-                .max_by_key(|(_, _, _, listing)| listing.price);
-            // info!("trade: {:?} -> {:?}", cheapest_buy, priciest_sell);
-            match (cheapest_buy, priciest_sell) {
-                (
-                    Some((buy_entity, _, buy_name, buy_listing)),
-                    Some((sell_entity, _, sell_name, sell_listing)),
-                ) if buy_listing.price < sell_listing.price => Some(TradeRoute {
-                    store_to_buy_from: *buy_entity,
-                    store_to_buy_from_name: buy_name.0.clone(),
-                    store_to_sell_to: *sell_entity,
-                    store_to_sell_to_name: sell_name.0.clone(),
-                    commodity,
-                    cost_to_buy_commodity: buy_listing.price,
-                    price_to_sell_commodity: sell_listing.price,
-                }),
-                _ => None,
+#[allow(clippy::type_complexity)]
+fn move_ship_towards_objective(
+    mut commands: Commands,
+    mut ships: Query<
+        (
+            Entity,
+            &Name,
+            &mut Transform,
+            &ActionQueue,
+            &Engine,
+            &mut Movement,
+            &Inventory,
+            &mut FuelTank,
+            Option<&Docking>,
+        ),
+        (With<Ship>, Without<Stranded>),
+    >,
+    places: Query<&Transform, Without<Ship>>,
+    orbits: Query<&Orbit>,
+    ports: Query<&DockingPort>,
+    time: Res<Time>,
+) {
+    for (
+        ship,
+        name,
+        mut ship_transform,
+        action_queue,
+        engine,
+        mut movement,
+        inventory,
+        mut tank,
+        docking,
+    ) in ships.iter_mut()
+    {
+        // heavy cargo makes the ship slow to get up to speed, and to stop
+        let handling = engine.handling(inventory.mass());
+        let position = ship_transform.translation.truncate();
+        let destination = match docking {
+            // the port holds docked ships in their slots
+            Some(Docking::Docked { .. } | Docking::Undocking { .. }) => {
+                movement.velocity = Vec2::ZERO;
+                continue;
             }
-        })
-        .max_by_key(|trade_route| {
-            trade_route.price_to_sell_commodity - trade_route.cost_to_buy_commodity
+            Some(Docking::Approaching { planet }) => {
+                let (Ok(port), Ok(planet)) = (ports.get(*planet), places.get(*planet)) else {
+                    continue;
+                };
+                let planet = planet.translation.truncate();
+                match port.slot_of(ship) {
+                    Some(slot) => Some(planet + port.slot_offset(slot)),
+                    // hold off the planet until a slot frees up
+                    None => Some(
+                        planet
+                            + (position - planet).normalize_or_zero()
+                                * (port.radius + APPROACH_DISTANCE / 2.),
+                    ),
+                }
+            }
+            // head for where a moving destination will be by the time the ship gets there
+            None => match action_queue.queue.first().and_then(ShipAction::destination) {
+                Some(Target::Entity(body)) if orbits.contains(body) => Some(
+                    intercept(
+                        position,
+                        |seconds| position_in(body, seconds, &orbits).unwrap_or_default(),
+                        |distance| {
+                            travel_time(distance, handling.max_speed, handling.max_acceleration)
+                        },
+                    )
+                    .0,
+                ),
+                _ => action_queue.destination(&places),
+            },
+        };
+        let step = match destination {
+            Some(destination) => {
+                movement.steer_towards(position, destination, &handling, time.delta_seconds())
+            }
+            None => movement.brake(&handling, time.delta_seconds()),
+        };
+        if tank
+            .burn(fuel_needed(
+                step.length(),
+                engine.hull_mass + inventory.mass(),
+            ))
+            .not()
+        {
+            warn!("[{}]: Out of fuel, stranded", name.0);
+            movement.velocity = Vec2::ZERO;
+            commands.entity(ship).insert(Stranded);
+            continue;
+        }
+        ship_transform.translation += step.extend(0.);
+    }
+}
+
+fn point_hulls_along_heading(
+    ships: Query<(&Movement, &Children), With<Ship>>,
+    mut hulls: Query<&mut Transform, With<Hull>>,
+) {
+    for (movement, children) in ships.iter() {
+        let mut hulls = hulls.iter_many_mut(children);
+        while let Some(mut transform) = hulls.fetch_next() {
+            transform.rotation = Quat::from_rotation_z(movement.heading);
+        }
+    }
+}
+
+/// What a rescuer charges for each unit of fuel, what it would cost at the cheapest station
+fn rescue_fuel_price(market: &Market) -> Credits {
+    market
+        .get_sellers(Commodity::Fuel)
+        .map(|listing| listing.price)
+        .min()
+        .unwrap_or_default()
+        .max(1)
+}
+
+/// Sends the closest ship carrying fuel as cargo to every stranded ship that can pay for some,
+/// unless one is on its way already
+#[allow(clippy::type_complexity)]
+fn dispatch_rescuers(
+    stranded: Query<(&Name, &Transform, &Wallet), With<Stranded>>,
+    mut rescuers: Query<
+        (&Name, &Transform, &Inventory, &mut ActionQueue),
+        (With<Ship>, Without<Stranded>),
+    >,
+    market: Res<Market>,
+) {
+    let price = rescue_fuel_price(&market);
+    for (name, transform, wallet) in stranded.iter() {
+        let position = transform.translation.truncate();
+        let on_its_way = rescuers
+            .iter()
+            .any(|(_, _, _, action_queue)| action_queue.is_rescuing(position));
+        if on_its_way || wallet.can_afford(price).not() {
+            continue;
+        }
+        let Some((rescuer, _, _, mut action_queue)) = rescuers
+            .iter_mut()
+            .filter(|(_, _, hold, _)| hold.get(&Commodity::Fuel) > 0)
+            .min_by(|(_, a, _, _), (_, b, _, _)| {
+                let distance = |other: &Transform| other.translation.truncate().distance(position);
+                distance(a).total_cmp(&distance(b))
+            })
+        else {
+            continue;
+        };
+        info!("[{}]: Flying out to rescue {}", rescuer.0, name.0);
+        action_queue
+            .queue
+            .insert(0, ShipAction::MoveTo(Target::Point(position)));
+    }
+}
+
+/// Ships carrying fuel as cargo sell it to stranded ships they pass, and planets sell fuel to
+/// stranded ships that drifted close enough
+#[allow(clippy::type_complexity)]
+fn rescue_stranded_ships(
+    mut commands: Commands,
+    mut stranded: Query<(Entity, &Name, &Transform, &mut FuelTank, &mut Wallet), With<Stranded>>,
+    mut rescuers: Query<
+        (&Transform, &mut Inventory, &mut Wallet, &mut ActionQueue),
+        (With<Ship>, Without<Stranded>),
+    >,
+    mut stores: Query<(&Transform, &mut Store), Without<Ship>>,
+    market: Res<Market>,
+    mut ledger: ResMut<Ledger>,
+) {
+    let price = rescue_fuel_price(&market);
+    for (ship, name, transform, mut tank, mut wallet) in stranded.iter_mut() {
+        let position = transform.translation.truncate();
+        let close_by =
+            |other: &Transform| other.translation.truncate().distance(position) < RESCUE_RANGE;
+        for (_, mut hold, mut rescuer_wallet, _) in rescuers
+            .iter_mut()
+            .filter(|(transform, ..)| close_by(transform))
+        {
+            let fuel = hold
+                .get(&Commodity::Fuel)
+                .min(tank.space_left())
+                .min(wallet.credits() / price);
+            if fuel == 0 {
+                continue;
+            }
+            ledger
+                .transfer(&mut wallet, &mut rescuer_wallet, fuel * price)
+                .expect("Only bought what it could afford");
+            tank.fill(hold.take(&Commodity::Fuel, fuel));
+        }
+        for (_, mut store) in stores
+            .iter_mut()
+            .filter(|(transform, _)| close_by(transform))
+        {
+            if let Some(receipt) = store.buy_from_store(
+                Commodity::Fuel,
+                tank.space_left(),
+                None,
+                OrderMode::Partial,
+                &mut wallet,
+                &mut ledger,
+            ) {
+                tank.fill(receipt.amount);
+            }
+        }
+        if tank.is_empty().not() {
+            info!("[{}]: Rescued with {:.1} fuel", name.0, tank.fuel());
+            commands.entity(ship).remove::<Stranded>();
+            // nobody needs to fly out here anymore
+            for (.., mut action_queue) in rescuers.iter_mut() {
+                action_queue
+                    .queue
+                    .retain(|action| action.is_rescue(position).not());
+            }
+        }
+    }
+}
+
+/// Everything that isn't a trade: moving, waiting, docking and patrolling
+#[allow(clippy::type_complexity)]
+fn carry_out_ship_actions(
+    mut ships: Query<(&Transform, &mut ActionQueue, &Movement, Option<&Docking>), With<Ship>>,
+    places: Query<&Transform, Without<Ship>>,
+    ports: Query<&DockingPort>,
+    stores: Query<&Store>,
+    time: Res<Time>,
+) {
+    for (ship_transform, mut action_queue, movement, docking) in ships.iter_mut() {
+        let position = ship_transform.translation.truncate();
+        // planets count as reached once the ship could ask for a slot, points once it's on them
+        let arrived = action_queue
+            .queue
+            .first()
+            .and_then(ShipAction::destination)
+            .is_some_and(|target| {
+                let Some(destination) = target.position(&places) else {
+                    return false;
+                };
+                match target {
+                    Target::Entity(entity) => {
+                        let radius = ports.get(entity).map_or(0., |port| port.radius);
+                        destination.distance(position) < radius + APPROACH_DISTANCE
+                    }
+                    Target::Point(_) => {
+                        destination.distance(position) < DOCKING_TOLERANCE && movement.is_at_rest()
+                    }
+                }
+            });
+        let more_queued = action_queue.queue.len() > 1;
+        let Some(action) = action_queue.queue.first_mut() else {
+            continue;
+        };
+        let in_stock = |store: Entity, commodity: Commodity, amount: Amount| {
+            stores
+                .get(store)
+                .ok()
+                .and_then(|store| store.quote_buy(commodity, amount))
+                .is_some_and(|quote| quote.amount >= amount)
+        };
+        let done = action.advance(
+            time.delta_seconds(),
+            arrived,
+            docking,
+            more_queued,
+            in_stock,
+        );
+        if done {
+            action_queue.queue.remove(0);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn trade_with_planet(
+    mut ships: Query<
+        (
+            Entity,
+            &mut ActionQueue,
+            &mut Inventory,
+            &mut Wallet,
+            &mut FuelTank,
+            &Engine,
+            Option<&mut Docking>,
+            &Automation,
+            Option<&Home>,
+        ),
+        With<Ship>,
+    >,
+    places: Query<&Transform, Without<Ship>>,
+    mut stores: Query<(&mut Store, Option<&mut OrderBook>), Without<Ship>>,
+    mut ledger: ResMut<Ledger>,
+    market: Res<Market>,
+    mut outcomes: EventWriter<ActionOutcome>,
+    mut waiting_on_order_book: Local<HashSet<Entity>>,
+    mut waiting_for_price: Local<HashMap<Entity, f32>>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    for (
+        ship,
+        mut action_queue,
+        mut inventory,
+        mut wallet,
+        mut tank,
+        engine,
+        docking,
+        automation,
+        home,
+    ) in ships.iter_mut()
+    {
+        let Some(action) = action_queue.queue.first().cloned() else {
+            continue;
+        };
+        let (Some(store_entity), Some(Target::Entity(destination_entity))) =
+            (action.store(), action.destination())
+        else {
+            continue;
+        };
+        // trades happen in a docking slot, one load at a time
+        let Some(mut docking) = docking else {
+            continue;
+        };
+        let Docking::Docked {
+            planet,
+            ref mut busy_for,
+        } = *docking
+        else {
+            continue;
+        };
+        if planet != destination_entity || *busy_for > 0. {
+            continue;
+        }
+
+        if waiting_for_price
+            .get(&ship)
+            .is_some_and(|until| *until > now)
+        {
+            continue;
+        }
+
+        // we're at the right planet, what was held for us is ours to trade now
+        let (mut store, order_book) = stores
+            .get_mut(store_entity)
+            .expect("Should be a store here");
+        if let Some(reservation) = action.reservation() {
+            store.release(reservation);
+        }
+
+        if let Some(mut order_book) = order_book {
+            // wait around until our order has been filled or has expired
+            if waiting_on_order_book.contains(&ship) {
+                if order_book.has_orders_from(ship).not() {
+                    waiting_on_order_book.remove(&ship);
+                    action_queue.queue.remove(0);
+                    outcomes.send(ActionOutcome {
+                        ship,
+                        planet: destination_entity,
+                        outcome: Outcome::OrderSettled,
+                    });
+                }
+                continue;
+            }
+            if post_to_order_book(
+                ship,
+                &action,
+                &store,
+                &mut order_book,
+                &mut inventory,
+                &mut wallet,
+                &mut ledger,
+                now + ORDER_TTL,
+            ) {
+                waiting_on_order_book.insert(ship);
+                continue;
+            }
+        }
+
+        let result = match action {
+            ShipAction::Buy {
+                commodity, amount, ..
+            } => buy(
+                &mut store,
+                commodity,
+                amount,
+                &mut inventory,
+                &mut wallet,
+                &mut ledger,
+            ),
+            ShipAction::Sell {
+                commodity,
+                min_price,
+                ..
+            } => sell(
+                &mut store,
+                commodity,
+                min_price,
+                &mut inventory,
+                &mut wallet,
+                &mut ledger,
+            ),
+            ShipAction::Refuel { amount, .. } => {
+                refuel(&mut store, amount, &mut tank, &mut wallet, &mut ledger)
+            }
+            _ => unreachable!("Only trades have a store"),
+        };
+
+        let reason = match result {
+            Ok(receipt) => {
+                *busy_for = LOAD_SECONDS_PER_UNIT * receipt.amount as f32;
+                // a partial sale leaves the rest of the cargo for another attempt
+                let done = match action {
+                    ShipAction::Sell { commodity, .. } => inventory.get(&commodity) == 0,
+                    _ => true,
+                };
+                if done {
+                    action_queue.queue.remove(0);
+                    waiting_for_price.remove(&ship);
+                }
+                outcomes.send(ActionOutcome {
+                    ship,
+                    planet: destination_entity,
+                    outcome: Outcome::Traded(receipt),
+                });
+                continue;
+            }
+            Err(reason) => reason,
+        };
+
+        let recovery = match action {
+            ShipAction::Sell {
+                commodity,
+                min_price,
+                ..
+            } => recover_from_failed_sale(
+                destination_entity,
+                commodity,
+                min_price,
+                reason,
+                store
+                    .quote_sell(commodity, 1)
+                    .map(|quote| quote.marginal_price),
+                waiting_for_price.contains_key(&ship),
+                &market,
+                &automation.constraints(
+                    home.and_then(|home| Target::Entity(home.planet).position(&places)),
+                ),
+                now,
+            ),
+            _ if reason == ActionFailure::TankFull => Recovery::Skip,
+            _ => Recovery::Replan,
+        };
+        outcomes.send(ActionOutcome {
+            ship,
+            planet: destination_entity,
+            outcome: Outcome::Failed { reason, recovery },
+        });
+
+        match recovery {
+            Recovery::Wait { until } => {
+                waiting_for_price.insert(ship, until);
+            }
+            Recovery::RetryLower { min_price: lower } => {
+                if let Some(ShipAction::Sell { min_price, .. }) = action_queue.queue.first_mut() {
+                    *min_price = lower;
+                }
+            }
+            Recovery::Reroute { planet, min_price } => {
+                waiting_for_price.remove(&ship);
+                let ShipAction::Sell { commodity, .. } = &action else {
+                    unreachable!("Only sales get rerouted")
+                };
+                let Ok((mut buyer, _)) = stores.get_mut(planet) else {
+                    continue;
+                };
+                let distance = Target::Entity(planet)
+                    .position(&places)
+                    .zip(Target::Entity(destination_entity).position(&places))
+                    .map_or(0., |(buyer, here)| buyer.distance(here));
+                let flight_time = travel_time(
+                    distance,
+                    engine.speed,
+                    engine.acceleration(inventory.mass()),
+                );
+                let amount = inventory.get(commodity);
+                let reservation = buyer.reserve(
+                    Side::Sell,
+                    *commodity,
+                    amount,
+                    now + flight_time * 2. + RESERVATION_GRACE,
+                );
+                action_queue.queue[0] = ShipAction::Sell {
+                    planet_to_sell_at: planet,
+                    store: planet,
+                    commodity: *commodity,
+                    min_price,
+                    reservation,
+                };
+            }
+            Recovery::StoreCargo => {
+                // keep it in the hold, it's sold off once a buyer shows up
+                waiting_for_price.remove(&ship);
+                action_queue.queue.remove(0);
+            }
+            Recovery::Skip => {
+                waiting_for_price.remove(&ship);
+                action_queue.queue.remove(0);
+            }
+            Recovery::Replan => {
+                waiting_for_price.remove(&ship);
+                action_queue.queue.remove(0);
+                // the rest of the queue stands, except for selling what was never bought
+                if let ShipAction::Buy { commodity, .. } = action {
+                    if inventory.get(&commodity) == 0 {
+                        let (unsellable, rest) =
+                            action_queue
+                                .queue
+                                .drain(..)
+                                .partition(|queued| match queued {
+                                    ShipAction::Sell {
+                                        commodity: sold, ..
+                                    } => *sold == commodity,
+                                    _ => false,
+                                });
+                        action_queue.queue = rest;
+                        for action in unsellable {
+                            if let (Some(store), Some(reservation)) =
+                                (action.store(), action.reservation())
+                            {
+                                if let Ok((mut store, _)) = stores.get_mut(store) {
+                                    store.release(reservation);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn buy(
+    store: &mut Store,
+    commodity: Commodity,
+    amount: Amount,
+    inventory: &mut Inventory,
+    wallet: &mut Wallet,
+    ledger: &mut Ledger,
+) -> Result<Receipt, ActionFailure> {
+    let amount = inventory.room_for(commodity).min(amount);
+    if amount == 0 {
+        return Err(ActionFailure::HoldFull);
+    }
+    let Some(receipt) =
+        store.buy_from_store(commodity, amount, None, OrderMode::Partial, wallet, ledger)
+    else {
+        return Err(if store.quote_buy(commodity, 1).is_none() {
+            ActionFailure::OutOfStock
+        } else {
+            ActionFailure::CantAfford
         });
-    trade_routes
+    };
+    inventory
+        .add(receipt.commodity, receipt.amount)
+        .expect("Didn't buy more than there was room for");
+    Ok(receipt)
+}
+
+fn refuel(
+    store: &mut Store,
+    amount: Amount,
+    tank: &mut FuelTank,
+    wallet: &mut Wallet,
+    ledger: &mut Ledger,
+) -> Result<Receipt, ActionFailure> {
+    let amount = tank.space_left().min(amount);
+    if amount == 0 {
+        return Err(ActionFailure::TankFull);
+    }
+    let Some(receipt) = store.buy_from_store(
+        Commodity::Fuel,
+        amount,
+        None,
+        OrderMode::Partial,
+        wallet,
+        ledger,
+    ) else {
+        return Err(if store.quote_buy(Commodity::Fuel, 1).is_none() {
+            ActionFailure::OutOfStock
+        } else {
+            ActionFailure::CantAfford
+        });
+    };
+    tank.fill(receipt.amount);
+    Ok(receipt)
+}
+
+fn sell(
+    store: &mut Store,
+    commodity: Commodity,
+    min_price: Credits,
+    inventory: &mut Inventory,
+    wallet: &mut Wallet,
+    ledger: &mut Ledger,
+) -> Result<Receipt, ActionFailure> {
+    let amount = inventory.get(&commodity);
+    if amount == 0 {
+        return Err(ActionFailure::NothingToSell);
+    }
+    let Some(receipt) = store.sell_to_store(
+        commodity,
+        amount,
+        Some(min_price),
+        OrderMode::Partial,
+        wallet,
+        ledger,
+    ) else {
+        return Err(if store.quote_sell(commodity, 1).is_none() {
+            ActionFailure::NoBuyer
+        } else {
+            ActionFailure::PriceTooLow
+        });
+    };
+    inventory.take(&receipt.commodity, receipt.amount);
+    Ok(receipt)
+}
+
+/// Waits for the price to recover once, then takes a somewhat lower price, then looks for a better
+/// buyer elsewhere that the ship is allowed to trade with. Cargo nobody wants stays in the hold
+#[allow(clippy::too_many_arguments)]
+fn recover_from_failed_sale(
+    planet: Entity,
+    commodity: Commodity,
+    min_price: Credits,
+    reason: ActionFailure,
+    bid: Option<Credits>,
+    already_waited: bool,
+    market: &Market,
+    constraints: &Constraints,
+    now: f32,
+) -> Recovery {
+    match reason {
+        ActionFailure::NothingToSell => return Recovery::Skip,
+        ActionFailure::PriceTooLow if already_waited.not() => {
+            return Recovery::Wait {
+                until: now + PRICE_PATIENCE,
+            }
+        }
+        _ => {}
+    }
+    if let Some(bid) = bid.filter(|bid| bid * 2 >= min_price) {
+        return Recovery::RetryLower { min_price: bid };
+    }
+    let elsewhere = market
+        .get_buyers(commodity)
+        .filter(|listing| listing.planet != planet && listing.amount > 0)
+        .filter(|listing| constraints.allows(listing))
+        .filter(|listing| bid.is_none_or(|bid| listing.price > bid))
+        .max_by_key(|listing| listing.price);
+    match (elsewhere, bid) {
+        (Some(listing), _) => Recovery::Reroute {
+            planet: listing.planet,
+            min_price: sale_floor(listing.price),
+        },
+        (None, Some(bid)) => Recovery::RetryLower { min_price: bid },
+        (None, None) => Recovery::StoreCargo,
+    }
+}
+
+/// The lowest unit price a ship accepts when it expected to get `unit_price`
+fn sale_floor(unit_price: Credits) -> Credits {
+    ((unit_price as f32 * (1. - PRICE_TOLERANCE)) as Credits).max(1)
+}
+
+/// Posts a limit order priced at the store's quote, so the ship does at least as well as trading
+/// with the store directly. Returns false if there was nothing worth posting
+#[allow(clippy::too_many_arguments)]
+fn post_to_order_book(
+    ship: Entity,
+    action: &ShipAction,
+    store: &Store,
+    order_book: &mut OrderBook,
+    inventory: &mut Inventory,
+    wallet: &mut Wallet,
+    ledger: &mut Ledger,
+    expires_at: f32,
+) -> bool {
+    match action {
+        ShipAction::Buy {
+            commodity, amount, ..
+        } => {
+            let Some(price) = store
+                .price_check_buy_specific_from_store(*commodity)
+                .map(|listing| listing.price)
+            else {
+                return false;
+            };
+            let amount = inventory
+                .room_for(*commodity)
+                .min(*amount)
+                .min(wallet.credits() / price);
+            amount > 0
+                && order_book
+                    .post_buy(ship, *commodity, amount, price, expires_at, wallet, ledger)
+                    .is_ok()
+        }
+        ShipAction::Sell { commodity, .. } => {
+            let amount = inventory.get(commodity);
+            let price = store
+                .price_check_sell_specific_to_store(*commodity)
+                .map(|listing| listing.price)
+                .or(order_book.best_bid(*commodity));
+            match price {
+                Some(price) if amount > 0 => {
+                    inventory.take(commodity, amount);
+                    order_book.post_sell(ship, *commodity, amount, price, expires_at);
+                    true
+                }
+                _ => false,
+            }
+        }
+        // refuelling is quick enough to skip the book
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HERE: Entity = Entity::from_raw(1);
+    const THERE: Entity = Entity::from_raw(2);
+
+    /// A store short of food, with `credits` to buy it with
+    fn hungry_store(ledger: &mut Ledger, credits: Credits) -> Store {
+        let mut store = Store::default();
+        store.take(Commodity::Food, 90);
+        ledger.mint(&mut store.wallet, credits);
+        store
+    }
+
+    fn market_with(stores: &[(Entity, &Store)]) -> Market {
+        let mut market = Market::default();
+        for (planet, store) in stores {
+            market.add_store(*planet, Vec2::ZERO, store);
+        }
+        market
+    }
+
+    fn recover(
+        reason: ActionFailure,
+        min_price: Credits,
+        bid: Option<Credits>,
+        already_waited: bool,
+        market: &Market,
+    ) -> Recovery {
+        recover_from_failed_sale(
+            HERE,
+            Commodity::Food,
+            min_price,
+            reason,
+            bid,
+            already_waited,
+            market,
+            &Constraints::default(),
+            10.,
+        )
+    }
+
+    #[test]
+    fn nothing_to_sell_is_skipped() {
+        let market = Market::default();
+
+        assert_eq!(
+            recover(ActionFailure::NothingToSell, 10, Some(10), false, &market),
+            Recovery::Skip
+        );
+    }
+
+    #[test]
+    fn waits_once_for_the_price_to_recover() {
+        let market = Market::default();
+
+        assert_eq!(
+            recover(ActionFailure::PriceTooLow, 10, Some(8), false, &market),
+            Recovery::Wait {
+                until: 10. + PRICE_PATIENCE
+            }
+        );
+    }
+
+    #[test]
+    fn takes_a_somewhat_lower_price_after_waiting() {
+        let market = Market::default();
+
+        assert_eq!(
+            recover(ActionFailure::PriceTooLow, 10, Some(8), true, &market),
+            Recovery::RetryLower { min_price: 8 }
+        );
+    }
+
+    #[test]
+    fn takes_a_much_lower_bid_elsewhere_instead() {
+        let mut ledger = Ledger::default();
+        let store = hungry_store(&mut ledger, 10_000);
+        let market = market_with(&[(THERE, &store)]);
+        let price = market
+            .get_buyers(Commodity::Food)
+            .next()
+            .expect("The store is short of food")
+            .price;
+
+        assert_eq!(
+            recover(
+                ActionFailure::PriceTooLow,
+                price * 4,
+                Some(1),
+                true,
+                &market
+            ),
+            Recovery::Reroute {
+                planet: THERE,
+                min_price: sale_floor(price),
+            }
+        );
+    }
+
+    #[test]
+    fn takes_a_much_lower_bid_when_nobody_else_pays_more() {
+        let market = Market::default();
+
+        assert_eq!(
+            recover(ActionFailure::PriceTooLow, 10, Some(1), true, &market),
+            Recovery::RetryLower { min_price: 1 }
+        );
+    }
+
+    #[test]
+    fn keeps_the_cargo_when_nobody_buys() {
+        let market = Market::default();
+
+        assert_eq!(
+            recover(ActionFailure::NoBuyer, 10, None, true, &market),
+            Recovery::StoreCargo
+        );
+    }
+
+    #[test]
+    fn doesnt_reroute_to_a_broke_buyer() {
+        let mut ledger = Ledger::default();
+        let store = hungry_store(&mut ledger, 0);
+        let market = market_with(&[(THERE, &store)]);
+
+        assert_eq!(
+            recover(ActionFailure::NoBuyer, 10, None, true, &market),
+            Recovery::StoreCargo
+        );
+    }
+
+    #[test]
+    fn doesnt_reroute_where_the_ship_isnt_allowed_to_trade() {
+        let mut ledger = Ledger::default();
+        let store = hungry_store(&mut ledger, 10_000);
+        let market = market_with(&[(THERE, &store)]);
+        let far_from_home = Automation::StayNearHome { range: 10. };
+        let only_fuel = Automation::TradeOnly(Commodity::Fuel);
+
+        for constraints in [
+            far_from_home.constraints(Some(Vec2::new(1000., 0.))),
+            only_fuel.constraints(None),
+        ] {
+            assert_eq!(
+                recover_from_failed_sale(
+                    HERE,
+                    Commodity::Food,
+                    10,
+                    ActionFailure::NoBuyer,
+                    None,
+                    true,
+                    &market,
+                    &constraints,
+                    10.,
+                ),
+                Recovery::StoreCargo
+            );
+        }
+    }
+
+    fn advance(action: &mut ShipAction, arrived: bool, docking: Option<&Docking>) -> bool {
+        action.advance(1., arrived, docking, false, |_, _, _| false)
+    }
+
+    #[test]
+    fn moves_until_it_arrives() {
+        let mut action = ShipAction::MoveTo(Target::Point(Vec2::new(10., 0.)));
+
+        assert_eq!(
+            action.destination(),
+            Some(Target::Point(Vec2::new(10., 0.)))
+        );
+        assert!(advance(&mut action, false, None).not());
+        assert!(advance(&mut action, true, None));
+    }
+
+    #[test]
+    fn waits_out_the_clock() {
+        let mut action = ShipAction::Wait(WaitFor::Duration(1.5));
+
+        assert_eq!(action.destination(), None);
+        assert!(advance(&mut action, false, None).not());
+        assert!(advance(&mut action, false, None));
+    }
+
+    #[test]
+    fn waits_until_theres_enough_for_sale() {
+        let mut action = ShipAction::Wait(WaitFor::Stock {
+            store: HERE,
+            commodity: Commodity::Fuel,
+            amount: 5,
+        });
+        let stock_of = |stock: Amount| {
+            move |store: Entity, commodity: Commodity, amount: Amount| {
+                store == HERE && commodity == Commodity::Fuel && stock >= amount
+            }
+        };
+
+        assert!(action.advance(1., false, None, false, stock_of(4)).not());
+        assert!(action.advance(1., false, None, false, stock_of(5)));
+    }
+
+    #[test]
+    fn stays_docked_until_theres_something_else_to_do() {
+        let mut action = ShipAction::Dock { planet: HERE };
+        let docked = Docking::Docked {
+            planet: HERE,
+            busy_for: 0.,
+        };
+        let elsewhere = Docking::Docked {
+            planet: THERE,
+            busy_for: 0.,
+        };
+
+        assert_eq!(action.destination(), Some(Target::Entity(HERE)));
+        assert_eq!(action.port(), Some(HERE));
+        let mut advance =
+            |docking, more_queued| action.advance(1., true, docking, more_queued, |_, _, _| false);
+        assert!(advance(None, true).not());
+        assert!(advance(Some(&elsewhere), true).not());
+        assert!(advance(Some(&docked), false).not());
+        assert!(advance(Some(&docked), true));
+    }
+
+    #[test]
+    fn undocks_once_the_slot_is_cleared() {
+        let mut action = ShipAction::Undock;
+        let undocking = Docking::Undocking {
+            planet: HERE,
+            seconds_left: 1.,
+        };
+
+        assert_eq!(action.destination(), None);
+        assert!(advance(&mut action, false, Some(&undocking)).not());
+        assert!(advance(&mut action, false, None));
+    }
+
+    #[test]
+    fn patrols_the_waypoints_over_and_over() {
+        let waypoints = vec![Target::Entity(HERE), Target::Entity(THERE)];
+        let mut action = ShipAction::patrol(waypoints).expect("Has waypoints");
+
+        for expected in [HERE, THERE, HERE] {
+            assert_eq!(action.destination(), Some(Target::Entity(expected)));
+            assert!(advance(&mut action, false, None).not());
+            assert!(advance(&mut action, true, None).not());
+        }
+    }
+
+    #[test]
+    fn patrols_need_somewhere_to_go() {
+        assert!(ShipAction::patrol(vec![]).is_none());
+    }
+
+    #[test]
+    fn refuels_at_the_planet() {
+        let action = ShipAction::Refuel {
+            planet: HERE,
+            store: HERE,
+            amount: 10,
+        };
+
+        assert_eq!(action.destination(), Some(Target::Entity(HERE)));
+        assert_eq!(action.port(), Some(HERE));
+        assert_eq!(action.store(), Some(HERE));
+    }
+
+    fn docked(busy_for: f32) -> Docking {
+        Docking::Docked {
+            planet: HERE,
+            busy_for,
+        }
+    }
+
+    #[test]
+    fn approaching_ships_dock_once_theyre_in_their_slot() {
+        let dock = ShipAction::Dock { planet: HERE };
+        let approaching = Docking::Approaching { planet: HERE };
+
+        assert!(matches!(
+            approaching.step(Some(&dock), false, 1.),
+            Some(Docking::Approaching { planet: HERE })
+        ));
+        assert!(matches!(
+            approaching.step(Some(&dock), true, 1.),
+            Some(Docking::Docked { planet: HERE, .. })
+        ));
+    }
+
+    #[test]
+    fn approaching_ships_get_out_of_line_when_plans_change() {
+        let elsewhere = ShipAction::Dock { planet: THERE };
+        let approaching = Docking::Approaching { planet: HERE };
+
+        assert!(approaching.step(Some(&elsewhere), true, 1.).is_none());
+        assert!(approaching.step(None, true, 1.).is_none());
+    }
+
+    #[test]
+    fn docked_ships_stay_while_theres_something_to_do_here() {
+        let trade_here = ShipAction::Refuel {
+            planet: HERE,
+            store: HERE,
+            amount: 1,
+        };
+        let wait = ShipAction::Wait(WaitFor::Duration(5.));
+
+        for action in [trade_here, wait] {
+            assert!(matches!(
+                docked(0.).step(Some(&action), true, 1.),
+                Some(Docking::Docked { planet: HERE, .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn docked_ships_leave_once_theres_nothing_to_do_here() {
+        let elsewhere = ShipAction::MoveTo(Target::Entity(THERE));
+
+        for action in [Some(&ShipAction::Undock), Some(&elsewhere), None] {
+            assert!(matches!(
+                docked(0.).step(action, true, 1.),
+                Some(Docking::Undocking { planet: HERE, .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn docked_ships_finish_loading_before_they_leave() {
+        let loading = docked(1.5).step(None, true, 1.);
+
+        assert!(matches!(loading, Some(Docking::Docked { .. })));
+        assert!(matches!(
+            loading.and_then(|docking| docking.step(None, true, 1.)),
+            Some(Docking::Undocking { .. })
+        ));
+    }
+
+    #[test]
+    fn undocking_frees_the_slot_after_a_while() {
+        let undocking = Docking::Undocking {
+            planet: HERE,
+            seconds_left: UNDOCK_SECONDS,
+        };
+
+        let halfway = undocking.step(None, false, UNDOCK_SECONDS / 2.);
+        assert!(matches!(halfway, Some(Docking::Undocking { .. })));
+        assert!(halfway
+            .and_then(|docking| docking.step(None, false, UNDOCK_SECONDS))
+            .is_none());
+    }
+
+    #[test]
+    fn orders_take_over_until_theyre_carried_out() {
+        let mut automation = Automation::TradeOnly(Commodity::Food);
+
+        assert!(automation.take_orders());
+        assert!(automation.on_autopilot().not());
+        assert!(automation.take_orders().not(), "Further orders queue up");
+
+        automation.resume();
+        assert!(matches!(automation, Automation::TradeOnly(Commodity::Food)));
+    }
+
+    #[test]
+    fn manual_ships_stay_off_autopilot() {
+        let mut automation = Automation::Manual;
+
+        assert!(automation.take_orders().not());
+        automation.resume();
+        assert!(automation.on_autopilot().not());
+    }
+
+    #[test]
+    fn new_standing_orders_wait_for_the_players_orders() {
+        let mut automation = Automation::Automatic;
+        automation.take_orders();
+
+        automation.stand(Automation::Manual);
+        assert!(matches!(automation, Automation::Orders { .. }));
+        automation.resume();
+        assert!(matches!(automation, Automation::Manual));
+    }
+
+    /// A farm with food and fuel to sell, and a city short of food, far enough apart that the
+    /// trip takes fuel
+    fn shuttle_stores(ledger: &mut Ledger, fuel: Amount) -> (Store, Store) {
+        let mut farm = Store::default();
+        farm.give(Commodity::Food, 300).expect("Farm has room");
+        if fuel > 0 {
+            farm.give(Commodity::Fuel, fuel).expect("Farm has room");
+        }
+        ledger.mint(&mut farm.wallet, 1000);
+        (farm, hungry_store(ledger, 10_000))
+    }
+
+    fn plan_shuttle(ship: &ShipProfile, farm: &Store, city: &Store) -> Vec<RouteStop> {
+        let route = [RouteLeg {
+            buy_at: HERE,
+            sell_at: THERE,
+            commodity: Commodity::Food,
+        }];
+        plan_route(
+            &route,
+            ship,
+            |planet| {
+                Some(if planet == HERE {
+                    Vec2::ZERO
+                } else {
+                    Vec2::new(3000., 0.)
+                })
+            },
+            |planet| Some(if planet == HERE { farm } else { city }),
+        )
+    }
+
+    fn shuttle(hold: &Inventory, fuel: f32, budget: Credits) -> ShipProfile<'_> {
+        ShipProfile {
+            position: Vec2::ZERO,
+            speed: 100.,
+            thrust: 1000.,
+            hull_mass: 10.,
+            hold,
+            budget,
+            constraints: Constraints::default(),
+            fuel,
+            tank_capacity: 100.,
+            fuel_price: 1.,
+        }
+    }
+
+    #[test]
+    fn shuttles_refuel_for_the_round_trip() {
+        let mut ledger = Ledger::default();
+        let (farm, city) = shuttle_stores(&mut ledger, 100);
+        let hold = Inventory::with_capacity(10);
+        let ship = shuttle(&hold, 1., 1000);
+
+        let stops = plan_shuttle(&ship, &farm, &city);
+
+        let round_trip = ship.fuel_needed(Vec2::ZERO, Vec2::new(3000., 0.), 10.)
+            + ship.fuel_needed(Vec2::new(3000., 0.), Vec2::ZERO, 0.);
+        assert_eq!(stops.len(), 1);
+        assert!(stops[0].refuel > 0);
+        assert!(stops[0].refuel as f32 + 1. >= round_trip, "{:?}", stops);
+    }
+
+    #[test]
+    fn shuttles_dont_set_out_without_the_fuel_to_deliver() {
+        let mut ledger = Ledger::default();
+        let (farm, city) = shuttle_stores(&mut ledger, 0);
+        let hold = Inventory::with_capacity(10);
+
+        assert!(plan_shuttle(&shuttle(&hold, 0.1, 1000), &farm, &city).is_empty());
+    }
+
+    #[test]
+    fn shuttles_only_buy_what_they_can_pay_for() {
+        let mut ledger = Ledger::default();
+        let (farm, city) = shuttle_stores(&mut ledger, 100);
+        let hold = Inventory::with_capacity(10);
+
+        assert!(plan_shuttle(&shuttle(&hold, 100., 0), &farm, &city).is_empty());
+        let price = farm
+            .quote_buy(Commodity::Food, 1)
+            .expect("Farm sells food")
+            .total;
+        let stops = plan_shuttle(&shuttle(&hold, 100., price * 3), &farm, &city);
+        assert_eq!(stops.first().map(|stop| stop.amount), Some(3));
+    }
 }
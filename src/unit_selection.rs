@@ -5,8 +5,12 @@ use strum::IntoEnumIterator;
 use crate::asset_loading::Sprites;
 use crate::camera::MainCamera;
 use crate::common_components::Name;
+use crate::ship::{Automation, Home};
 use crate::v2::commodity::Commodity;
+use crate::v2::fuel::FuelTank;
 use crate::v2::inventory::Inventory;
+use crate::v2::ledger::Wallet;
+use crate::v2::population::Population;
 use crate::v2::store::Store;
 
 #[derive(Component)]
@@ -28,6 +32,7 @@ pub(crate) struct Selectable {
 #[derive(Component)]
 struct SelectionBox;
 
+#[allow(clippy::too_many_arguments)]
 fn click_to_select_system(
     windows: Query<&Window, With<PrimaryWindow>>,
     selectables: Query<(Entity, &Transform), With<Selectable>>,
@@ -37,8 +42,13 @@ fn click_to_select_system(
     camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
     mut commands: Commands,
     sprites: Res<Sprites>,
+    buttons: Query<&Interaction, With<Button>>,
 ) {
-    if mouse.just_pressed(MouseButton::Left) {
+    // clicks on the ui aren't meant for the map
+    let over_ui = buttons
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    if mouse.just_pressed(MouseButton::Left) && !over_ui {
         if let Some((camera, camera_transform)) = Some(camera_query.single()) {
             let mut clicked_entity = None;
             for (entity, transform) in selectables.iter() {
@@ -123,7 +133,6 @@ pub(crate) fn add_selected_unit_info_panel(parent: &mut ChildBuilder, font: Hand
                             font,
                             font_size: 16.,
                             color: Color::BLACK,
-                            ..Default::default()
                         },
                     ),
                     ..Default::default()
@@ -132,19 +141,43 @@ pub(crate) fn add_selected_unit_info_panel(parent: &mut ChildBuilder, font: Hand
         });
 }
 
+#[allow(clippy::type_complexity)]
 fn update_info_panel_system(
     mut info_box_query: Query<&mut Text, With<SelectedEntityInfoPanel>>,
-    selected_entity_query: Query<(&Selectable, &Name, Option<&Store>, Option<&Inventory>)>,
+    selected_entity_query: Query<(
+        &Selectable,
+        &Name,
+        Option<&Store>,
+        Option<&Inventory>,
+        Option<&Wallet>,
+        Option<&Automation>,
+        Option<&Home>,
+        Option<&FuelTank>,
+        Option<&Population>,
+    )>,
+    names: Query<&Name>,
 ) {
-    if let Some((_selectable, name, maybe_store, maybe_inventory)) = selected_entity_query
+    if let Some((
+        _selectable,
+        name,
+        maybe_store,
+        maybe_inventory,
+        maybe_wallet,
+        maybe_automation,
+        maybe_home,
+        maybe_tank,
+        maybe_population,
+    )) = selected_entity_query
         .iter()
-        .find(|(selectable, _name, _, _)| selectable.selected)
+        .find(|(selectable, ..)| selectable.selected)
     {
         if let Some(mut text) = info_box_query.iter_mut().next() {
             let text = text.sections.get_mut(0).unwrap();
             text.value = format!("Selected {}", name);
 
             if let Some(store) = maybe_store {
+                text.value
+                    .push_str(&format!("\nCredits: {}", store.wallet.credits()));
                 text.value.push_str("\nX Name B/S");
                 for commodity in Commodity::iter() {
                     let amount = store.inventory.get(&commodity);
@@ -174,6 +207,35 @@ fn update_info_panel_system(
                 //     store.inventory.get(&Commodity::Fuel)
                 // ));
             }
+            if let Some(population) = maybe_population {
+                text.value.push_str(&format!(
+                    "\nPopulation: {:.1}k, unrest {:.0}%",
+                    population.size(),
+                    population.unrest() * 100.
+                ));
+            }
+            if let Some(wallet) = maybe_wallet.filter(|_| maybe_store.is_none()) {
+                text.value
+                    .push_str(&format!("\nCredits: {}", wallet.credits()));
+            }
+            if let Some(automation) = maybe_automation {
+                let state = if automation.on_autopilot() {
+                    "on"
+                } else {
+                    "off"
+                };
+                text.value.push_str(&format!("\nAutopilot: {}", state));
+            }
+            if let Some(home) = maybe_home.and_then(|home| names.get(home.planet).ok()) {
+                text.value.push_str(&format!("\nHome: {}", home));
+            }
+            if let Some(tank) = maybe_tank {
+                text.value.push_str(&format!(
+                    "\nFuel tank: {:.1}/{}",
+                    tank.fuel(),
+                    tank.capacity
+                ));
+            }
             if let Some(inventory) = maybe_inventory {
                 text.value
                     .push_str(&format!("\nFood: {}", inventory.get(&Commodity::Food)));
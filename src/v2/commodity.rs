@@ -1,9 +1,31 @@
 use strum_macros::Display;
 use strum_macros::EnumIter;
 
+use crate::v2::inventory::Amount;
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, EnumIter, Display)]
 pub enum Commodity {
     Food,
     HydrogenTanks,
     Fuel,
 }
+
+impl Commodity {
+    /// How much hold space one unit takes up
+    pub const fn volume(&self) -> Amount {
+        match self {
+            Commodity::Food => 1,
+            Commodity::HydrogenTanks => 4,
+            Commodity::Fuel => 1,
+        }
+    }
+
+    /// How heavy one unit is, heavier cargo makes for slower ships
+    pub const fn mass(&self) -> f32 {
+        match self {
+            Commodity::Food => 1.,
+            Commodity::HydrogenTanks => 1.,
+            Commodity::Fuel => 2.,
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::v2::commodity::Commodity;
+use crate::v2::inventory::Amount;
+use crate::v2::store::Credits;
+
+/// How much of a commodity people buy depending on its price
+#[derive(Debug, Clone, Copy)]
+pub struct DemandCurve {
+    /// Units a thousand people buy every second at the reference price
+    pub per_thousand: f32,
+    pub reference_price: Credits,
+    /// How strongly people react to the price. At 1 they buy half as much at twice the price,
+    /// at 0 they don't care what it costs
+    pub elasticity: f32,
+    /// The most anyone pays for a unit, above it they go without
+    pub max_price: Credits,
+}
+
+impl DemandCurve {
+    pub const fn new(
+        per_thousand: f32,
+        reference_price: Credits,
+        elasticity: f32,
+        max_price: Credits,
+    ) -> Self {
+        Self {
+            per_thousand,
+            reference_price,
+            elasticity,
+            max_price,
+        }
+    }
+
+    /// Units a thousand people buy every second at `price`
+    pub fn quantity_at(&self, price: Credits) -> f32 {
+        if price > self.max_price {
+            return 0.;
+        }
+        let relative_price = price.max(1) as f32 / self.reference_price.max(1) as f32;
+        self.per_thousand * relative_price.powf(-self.elasticity)
+    }
+}
+
+/// Seconds of unmet demand people hold on to, they don't make up for more than that once goods
+/// turn up
+const DEMAND_MEMORY: f32 = 10.;
+
+/// What a planet's population buys, and how much it cares about the price of each
+#[derive(Debug, Default, Clone, Component)]
+pub struct Demand {
+    curves: HashMap<Commodity, DemandCurve>,
+    /// Units wanted but not bought yet, people only buy whole units
+    unmet: HashMap<Commodity, f32>,
+}
+
+impl Demand {
+    pub fn with(mut self, commodity: Commodity, curve: DemandCurve) -> Self {
+        self.curves.insert(commodity, curve);
+        self
+    }
+
+    pub fn commodities(&self) -> Vec<Commodity> {
+        self.curves.keys().copied().collect()
+    }
+
+    /// Whether anyone buys `commodity` at all at `price`
+    pub fn will_pay(&self, commodity: Commodity, price: Credits) -> bool {
+        self.curves
+            .get(&commodity)
+            .is_some_and(|curve| price <= curve.max_price)
+    }
+
+    /// Adds what `thousands` of people want over `seconds` at `price` to what they still want
+    pub fn accrue(&mut self, commodity: Commodity, price: Credits, thousands: f32, seconds: f32) {
+        let Some(curve) = self.curves.get(&commodity) else {
+            return;
+        };
+        let rate = curve.quantity_at(price) * thousands;
+        let unmet = self.unmet.entry(commodity).or_default();
+        *unmet = (*unmet + rate * seconds).min(rate * DEMAND_MEMORY);
+    }
+
+    /// Whole units people want to buy right now
+    pub fn wanted(&self, commodity: Commodity) -> Amount {
+        self.unmet.get(&commodity).copied().unwrap_or(0.).floor() as Amount
+    }
+
+    /// Takes what was bought, or ordered, off what people want
+    pub fn bought(&mut self, commodity: Commodity, amount: Amount) {
+        if let Some(unmet) = self.unmet.get_mut(&commodity) {
+            *unmet = (*unmet - amount as f32).max(0.);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Not;
+
+    use super::*;
+
+    #[test]
+    fn buys_more_when_cheap_and_less_when_dear() {
+        let curve = DemandCurve::new(1., 10, 1., 30);
+
+        assert_eq!(curve.quantity_at(10), 1.);
+        assert_eq!(curve.quantity_at(5), 2.);
+        assert_eq!(curve.quantity_at(20), 0.5);
+    }
+
+    #[test]
+    fn inelastic_demand_ignores_the_price() {
+        let curve = DemandCurve::new(1., 10, 0., 30);
+
+        assert_eq!(curve.quantity_at(1), 1.);
+        assert_eq!(curve.quantity_at(30), 1.);
+    }
+
+    #[test]
+    fn nothing_above_the_ceiling() {
+        let curve = DemandCurve::new(1., 10, 0., 30);
+
+        assert_eq!(curve.quantity_at(31), 0.);
+    }
+
+    #[test]
+    fn fractions_add_up_to_whole_units() {
+        let mut demand =
+            Demand::default().with(Commodity::Fuel, DemandCurve::new(0.25, 10, 1., 30));
+
+        let mut bought = 0;
+        for _ in 0..8 {
+            demand.accrue(Commodity::Fuel, 10, 1., 1.);
+            let wanted = demand.wanted(Commodity::Fuel);
+            demand.bought(Commodity::Fuel, wanted);
+            bought += wanted;
+        }
+
+        assert_eq!(bought, 2);
+        assert_eq!(demand.wanted(Commodity::Food), 0);
+    }
+
+    #[test]
+    fn what_wasnt_bought_is_still_wanted() {
+        let mut demand = Demand::default().with(Commodity::Food, DemandCurve::new(1., 10, 1., 30));
+
+        demand.accrue(Commodity::Food, 10, 1., 1.);
+        demand.accrue(Commodity::Food, 10, 1., 1.);
+        assert_eq!(demand.wanted(Commodity::Food), 2);
+
+        demand.bought(Commodity::Food, 1);
+        assert_eq!(demand.wanted(Commodity::Food), 1);
+
+        // but only for so long
+        for _ in 0..100 {
+            demand.accrue(Commodity::Food, 10, 1., 1.);
+        }
+        assert_eq!(demand.wanted(Commodity::Food), 10);
+    }
+
+    #[test]
+    fn nobody_wants_it_above_the_ceiling() {
+        let mut demand = Demand::default().with(Commodity::Food, DemandCurve::new(1., 10, 1., 30));
+        demand.accrue(Commodity::Food, 10, 1., 5.);
+
+        demand.accrue(Commodity::Food, 31, 1., 1.);
+
+        assert!(demand.will_pay(Commodity::Food, 31).not());
+        assert_eq!(demand.wanted(Commodity::Food), 0);
+    }
+}
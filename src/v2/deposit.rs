@@ -0,0 +1,172 @@
+use std::ops::Not;
+
+use bevy::prelude::*;
+
+use crate::v2::commodity::Commodity;
+use crate::v2::inventory::Amount;
+
+/// Something in the ground that can be extracted, until it runs out
+#[derive(Debug, Clone)]
+pub struct Deposit {
+    pub commodity: Commodity,
+    reserves: f32,
+    /// Reserves when it was found, a regenerating deposit never grows back past this
+    size: f32,
+    /// Units that grow back every second, 0 for deposits that are gone once they're used up
+    pub regeneration: f32,
+}
+
+impl Deposit {
+    pub fn new(commodity: Commodity, size: f32, regeneration: f32) -> Self {
+        Self {
+            commodity,
+            reserves: size,
+            size,
+            regeneration,
+        }
+    }
+
+    pub fn reserves(&self) -> f32 {
+        self.reserves
+    }
+
+    /// Fraction of the full extraction rate the deposit still gives, it falls as it's depleted
+    pub fn yield_factor(&self) -> f32 {
+        if self.size <= 0. {
+            return 0.;
+        }
+        (self.reserves / self.size).clamp(0., 1.)
+    }
+
+    pub fn is_depleted(&self) -> bool {
+        self.reserves < 1.
+    }
+
+    fn extractable(&self, amount: Amount) -> Amount {
+        if self.is_depleted() || amount == 0 {
+            return 0;
+        }
+        (amount as f32 * self.yield_factor())
+            .max(1.)
+            .min(self.reserves)
+            .floor() as Amount
+    }
+}
+
+/// What's in the ground at a planet
+#[derive(Debug, Default, Clone, Component)]
+pub struct Deposits {
+    deposits: Vec<Deposit>,
+}
+
+impl Deposits {
+    pub fn new(deposits: Vec<Deposit>) -> Self {
+        Self { deposits }
+    }
+
+    pub fn discover(&mut self, deposit: Deposit) {
+        self.deposits.push(deposit);
+    }
+
+    /// How much of `amount` would come out of the richest deposit of `commodity`, the more
+    /// depleted it is the less. Always at least a unit while there's a whole one left, so
+    /// deposits do run out
+    pub fn extractable(&self, commodity: Commodity, amount: Amount) -> Amount {
+        self.richest(commodity)
+            .map_or(0, |index| self.deposits[index].extractable(amount))
+    }
+
+    /// Takes what's `extractable` out of the richest deposit of `commodity`. Returns how much was
+    /// extracted
+    pub fn extract(&mut self, commodity: Commodity, amount: Amount) -> Amount {
+        let Some(index) = self.richest(commodity) else {
+            return 0;
+        };
+        let deposit = &mut self.deposits[index];
+        let extracted = deposit.extractable(amount);
+        deposit.reserves -= extracted as f32;
+        extracted
+    }
+
+    /// Index of the richest deposit of `commodity`
+    fn richest(&self, commodity: Commodity) -> Option<usize> {
+        self.deposits
+            .iter()
+            .enumerate()
+            .filter(|(_, deposit)| deposit.commodity == commodity)
+            .max_by(|(_, a), (_, b)| a.yield_factor().total_cmp(&b.yield_factor()))
+            .map(|(index, _)| index)
+    }
+
+    /// Grows back what regenerates, and forgets deposits that are used up for good
+    pub fn regenerate(&mut self, seconds: f32) {
+        for deposit in self.deposits.iter_mut() {
+            deposit.reserves =
+                (deposit.reserves + deposit.regeneration * seconds).min(deposit.size);
+        }
+        self.deposits
+            .retain(|deposit| deposit.is_depleted().not() || deposit.regeneration > 0.);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extraction_slows_as_the_deposit_runs_dry() {
+        let mut deposits = Deposits::new(vec![Deposit::new(Commodity::HydrogenTanks, 100., 0.)]);
+
+        assert_eq!(deposits.extract(Commodity::HydrogenTanks, 20), 20);
+        assert_eq!(deposits.extract(Commodity::HydrogenTanks, 20), 16);
+        assert_eq!(deposits.extractable(Commodity::HydrogenTanks, 20), 12);
+        assert_eq!(deposits.extract(Commodity::Food, 20), 0);
+    }
+
+    #[test]
+    fn deposits_run_out_however_slow_extraction_gets() {
+        let mut deposits = Deposits::new(vec![Deposit::new(Commodity::HydrogenTanks, 100., 0.)]);
+
+        let mut extracted = 0;
+        for _ in 0..1000 {
+            extracted += deposits.extract(Commodity::HydrogenTanks, 10);
+        }
+
+        assert_eq!(extracted, 100);
+        assert_eq!(deposits.extractable(Commodity::HydrogenTanks, 10), 0);
+        deposits.regenerate(1.);
+        assert!(deposits.deposits.is_empty());
+    }
+
+    #[test]
+    fn takes_from_the_richest_deposit() {
+        let mut deposits = Deposits::new(vec![Deposit::new(Commodity::Food, 100., 0.)]);
+        deposits.extract(Commodity::Food, 50);
+        deposits.discover(Deposit::new(Commodity::Food, 100., 0.));
+
+        assert_eq!(deposits.extract(Commodity::Food, 10), 10);
+    }
+
+    #[test]
+    fn soil_grows_back_but_never_past_its_size() {
+        let mut deposits = Deposits::new(vec![Deposit::new(Commodity::Food, 100., 5.)]);
+        deposits.extract(Commodity::Food, 50);
+
+        deposits.regenerate(4.);
+        assert_eq!(deposits.extractable(Commodity::Food, 10), 7);
+
+        deposits.regenerate(100.);
+        assert_eq!(deposits.extractable(Commodity::Food, 10), 10);
+    }
+
+    #[test]
+    fn used_up_deposits_are_forgotten() {
+        let mut deposits = Deposits::new(vec![Deposit::new(Commodity::HydrogenTanks, 1., 0.)]);
+        deposits.extract(Commodity::HydrogenTanks, 1);
+
+        deposits.regenerate(1.);
+
+        assert_eq!(deposits.extractable(Commodity::HydrogenTanks, 1), 0);
+        assert!(deposits.deposits.is_empty());
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::VecDeque;
+use std::f32::consts::TAU;
+use std::ops::Not;
+
+use bevy::prelude::*;
+
+/// A planet's docking slots, spread evenly on a ring around it, and the line of ships waiting
+/// for one to free up
+#[derive(Debug, Component)]
+pub struct DockingPort {
+    slots: Vec<Option<Entity>>,
+    /// Distance from the planet's centre to the slots
+    pub radius: f32,
+    queue: VecDeque<Entity>,
+}
+
+impl DockingPort {
+    pub fn new(slots: usize, radius: f32) -> Self {
+        Self {
+            slots: vec![None; slots.max(1)],
+            radius,
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// Hands out a free slot, first come first served. Ships that don't get one are put in line
+    pub fn request(&mut self, ship: Entity) -> Option<usize> {
+        if let Some(slot) = self.slot_of(ship) {
+            return Some(slot);
+        }
+        let first_in_line = self.queue.front().is_none_or(|first| *first == ship);
+        match self.slots.iter().position(Option::is_none) {
+            Some(slot) if first_in_line => {
+                self.queue.retain(|queued| *queued != ship);
+                self.slots[slot] = Some(ship);
+                Some(slot)
+            }
+            _ => {
+                if self.queue.contains(&ship).not() {
+                    self.queue.push_back(ship);
+                }
+                None
+            }
+        }
+    }
+
+    /// Frees the ship's slot, or its place in line
+    pub fn leave(&mut self, ship: Entity) {
+        for slot in self.slots.iter_mut().filter(|slot| **slot == Some(ship)) {
+            *slot = None;
+        }
+        self.queue.retain(|queued| *queued != ship);
+    }
+
+    pub fn slot_of(&self, ship: Entity) -> Option<usize> {
+        self.slots.iter().position(|slot| *slot == Some(ship))
+    }
+
+    /// How many ships are ahead in line, `None` if the ship isn't waiting
+    pub fn place_in_line(&self, ship: Entity) -> Option<usize> {
+        self.queue.iter().position(|queued| *queued == ship)
+    }
+
+    /// Where a slot is relative to the planet's centre
+    pub fn slot_offset(&self, slot: usize) -> Vec2 {
+        Vec2::from_angle(TAU * slot as f32 / self.slots.len() as f32) * self.radius
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hands_out_slots_until_full() {
+        let mut port = DockingPort::new(2, 30.);
+
+        assert_eq!(port.request(Entity::from_raw(1)), Some(0));
+        assert_eq!(port.request(Entity::from_raw(2)), Some(1));
+        assert_eq!(port.request(Entity::from_raw(3)), None);
+        assert_eq!(port.place_in_line(Entity::from_raw(3)), Some(0));
+
+        // asking again doesn't hand out another slot
+        assert_eq!(port.request(Entity::from_raw(1)), Some(0));
+    }
+
+    #[test]
+    fn freed_slots_go_to_whoever_waited_longest() {
+        let mut port = DockingPort::new(1, 30.);
+        let docked = Entity::from_raw(1);
+        let first = Entity::from_raw(2);
+        let second = Entity::from_raw(3);
+        port.request(docked);
+        port.request(first);
+        port.request(second);
+
+        port.leave(docked);
+
+        assert_eq!(port.request(second), None);
+        assert_eq!(port.request(first), Some(0));
+        assert_eq!(port.place_in_line(second), Some(0));
+    }
+
+    #[test]
+    fn leaving_the_line_lets_the_next_ship_in() {
+        let mut port = DockingPort::new(1, 30.);
+        let docked = Entity::from_raw(1);
+        let gave_up = Entity::from_raw(2);
+        let patient = Entity::from_raw(3);
+        port.request(docked);
+        port.request(gave_up);
+        port.request(patient);
+
+        port.leave(gave_up);
+        port.leave(docked);
+
+        assert_eq!(port.request(patient), Some(0));
+    }
+
+    #[test]
+    fn slots_are_spread_around_the_planet() {
+        let port = DockingPort::new(4, 30.);
+
+        assert!(port.slot_offset(0).distance(Vec2::new(30., 0.)) < 0.01);
+        assert!(port.slot_offset(1).distance(Vec2::new(0., 30.)) < 0.01);
+        assert!(port.slot_offset(2).distance(Vec2::new(-30., 0.)) < 0.01);
+    }
+}
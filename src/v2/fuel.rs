@@ -0,0 +1,90 @@
+use bevy::prelude::*;
+
+use crate::v2::inventory::Amount;
+
+/// Fuel burnt per unit of distance for every unit of mass moved
+pub const BURN_RATE: f32 = 0.0001;
+
+/// Fuel it takes to move `mass` over `distance`
+pub fn fuel_needed(distance: f32, mass: f32) -> f32 {
+    distance * mass * BURN_RATE
+}
+
+#[derive(Debug, Clone, Copy, Component)]
+pub struct FuelTank {
+    fuel: f32,
+    pub capacity: f32,
+}
+
+impl FuelTank {
+    /// A tank filled to the brim
+    pub const fn full(capacity: f32) -> Self {
+        Self {
+            fuel: capacity,
+            capacity,
+        }
+    }
+
+    pub const fn fuel(&self) -> f32 {
+        self.fuel
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fuel <= 0.
+    }
+
+    /// Whole units of fuel that still fit
+    pub fn space_left(&self) -> Amount {
+        (self.capacity - self.fuel).max(0.) as Amount
+    }
+
+    pub fn fill(&mut self, amount: Amount) {
+        self.fuel = (self.fuel + amount as f32).min(self.capacity);
+    }
+
+    /// Burns `amount` if there's that much in the tank, otherwise burns nothing
+    pub fn burn(&mut self, amount: f32) -> bool {
+        if amount > self.fuel {
+            return false;
+        }
+        self.fuel -= amount;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Not;
+
+    use super::*;
+
+    #[test]
+    fn heavier_and_further_burns_more() {
+        let short_and_light = fuel_needed(100., 10.);
+        assert!(fuel_needed(200., 10.) > short_and_light);
+        assert!(fuel_needed(100., 20.) > short_and_light);
+    }
+
+    #[test]
+    fn cant_burn_more_than_is_in_the_tank() {
+        let mut tank = FuelTank::full(10.);
+
+        assert!(tank.burn(6.));
+        assert!(tank.burn(6.).not());
+        assert_eq!(tank.fuel(), 4.);
+        assert!(tank.burn(4.));
+        assert!(tank.is_empty());
+    }
+
+    #[test]
+    fn fills_up_to_capacity() {
+        let mut tank = FuelTank::full(10.);
+        tank.burn(2.5);
+        assert_eq!(tank.space_left(), 2);
+
+        tank.fill(5);
+
+        assert_eq!(tank.fuel(), 10.);
+        assert_eq!(tank.space_left(), 0);
+    }
+}
@@ -7,10 +7,32 @@ use crate::v2::commodity::Commodity;
 
 pub(crate) type Amount = u64;
 
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum InventoryError {
+    ZeroAmount,
+    OverCapacity {
+        requested: Amount,
+        space_left: Amount,
+    },
+}
+
+/// What to do when more is added than there's room for
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum OverflowPolicy {
+    /// Refuse the whole amount
+    #[default]
+    Reject,
+    /// Add what fits, the rest stays with whoever tried to add it
+    Clamp,
+    /// Add what fits, the rest is lost
+    Spill,
+}
+
 #[derive(Component)]
 pub struct Inventory {
     pub items: HashMap<Commodity, Amount>,
     pub capacity: Amount,
+    pub overflow: OverflowPolicy,
 }
 
 impl Default for Inventory {
@@ -18,6 +40,7 @@ impl Default for Inventory {
         Self {
             items: HashMap::new(),
             capacity: 100,
+            overflow: OverflowPolicy::default(),
         }
     }
 }
@@ -26,18 +49,53 @@ impl Inventory {
     pub fn with_food_and_capacity(food: Amount, capacity: Amount) -> Self {
         let mut items = HashMap::new();
         items.insert(Commodity::Food, food);
-        Inventory { items, capacity }
+        Inventory {
+            items,
+            capacity,
+            overflow: OverflowPolicy::default(),
+        }
     }
     pub fn with_capacity(capacity: Amount) -> Self {
+        Self::with_capacity_and_overflow(capacity, OverflowPolicy::default())
+    }
+    pub fn with_capacity_and_overflow(capacity: Amount, overflow: OverflowPolicy) -> Self {
         Inventory {
             items: HashMap::new(),
             capacity,
+            overflow,
         }
     }
 
-    pub fn add(&mut self, commodity: Commodity, amount: Amount) {
-        // let amount = cmp::min(amount, self.space_left());
-        *self.items.entry(commodity).or_insert(0) += amount;
+    /// Returns how much of `amount` the inventory took, for `Spill` that includes what was lost
+    pub fn add(&mut self, commodity: Commodity, amount: Amount) -> Result<Amount, InventoryError> {
+        if amount == 0 {
+            return Err(InventoryError::ZeroAmount);
+        }
+        let room_for = self.room_for(commodity);
+        if amount <= room_for {
+            self.store(commodity, amount);
+            return Ok(amount);
+        }
+        match self.overflow {
+            OverflowPolicy::Clamp if room_for > 0 => {
+                self.store(commodity, room_for);
+                Ok(room_for)
+            }
+            OverflowPolicy::Spill => {
+                self.store(commodity, room_for);
+                Ok(amount)
+            }
+            OverflowPolicy::Reject | OverflowPolicy::Clamp => Err(InventoryError::OverCapacity {
+                requested: amount,
+                space_left: self.space_left(),
+            }),
+        }
+    }
+
+    fn store(&mut self, commodity: Commodity, amount: Amount) {
+        if amount > 0 {
+            *self.items.entry(commodity).or_insert(0) += amount;
+        }
     }
 
     pub fn take(&mut self, commodity: &Commodity, amount: Amount) -> Amount {
@@ -61,13 +119,26 @@ impl Inventory {
         *self.items.get(commodity).unwrap_or(&0)
     }
 
-    pub fn discard(&mut self, commodity: Commodity) {
-        *self.items.entry(commodity).or_insert(0) = 0;
+    /// Free hold space, measured in volume rather than units
+    pub fn space_left(&self) -> Amount {
+        let size: Amount = self
+            .items
+            .iter()
+            .map(|(commodity, amount)| commodity.volume() * amount)
+            .sum();
+        self.capacity.saturating_sub(size)
+    }
+
+    /// How many units of `commodity` fit in the space left
+    pub fn room_for(&self, commodity: Commodity) -> Amount {
+        self.space_left() / commodity.volume()
     }
 
-    pub fn space_left(&self) -> Amount {
-        let size: Amount = self.items.values().sum();
-        self.capacity - size
+    pub fn mass(&self) -> f32 {
+        self.items
+            .iter()
+            .map(|(commodity, amount)| commodity.mass() * *amount as f32)
+            .sum()
     }
 }
 
@@ -87,7 +158,7 @@ mod tests {
     #[test]
     fn add_remove_commodities() {
         let mut inventory = Inventory::with_capacity(10);
-        inventory.add(Commodity::Food, 3);
+        inventory.add(Commodity::Food, 3).expect("Should fit");
 
         assert_eq!(inventory.space_left(), 7);
         assert_eq!(inventory.get(&Commodity::Food), 3);
@@ -97,7 +168,7 @@ mod tests {
     #[test]
     fn remove_not_all_commodities() {
         let mut inventory = Inventory::with_capacity(10);
-        inventory.add(Commodity::Food, 10);
+        inventory.add(Commodity::Food, 10).expect("Should fit");
 
         assert_eq!(inventory.take(&Commodity::Food, 5), 5);
         assert_eq!(inventory.get(&Commodity::Food), 5);
@@ -111,4 +182,86 @@ mod tests {
         assert_eq!(inventory.get(&Commodity::Food), 0);
         assert_eq!(inventory.space_left(), 10);
     }
+
+    #[test]
+    fn cant_add_nothing() {
+        let mut inventory = Inventory::with_capacity(10);
+
+        assert_eq!(
+            inventory.add(Commodity::Food, 0),
+            Err(InventoryError::ZeroAmount)
+        );
+    }
+
+    #[test]
+    fn reject_overflow() {
+        let mut inventory = Inventory::with_capacity(10);
+        inventory.add(Commodity::Food, 8).expect("Should fit");
+
+        assert_eq!(
+            inventory.add(Commodity::Fuel, 3),
+            Err(InventoryError::OverCapacity {
+                requested: 3,
+                space_left: 2,
+            })
+        );
+        assert_eq!(inventory.get(&Commodity::Fuel), 0);
+        assert_eq!(inventory.space_left(), 2);
+    }
+
+    #[test]
+    fn clamp_overflow() {
+        let mut inventory = Inventory::with_capacity_and_overflow(10, OverflowPolicy::Clamp);
+        inventory.add(Commodity::Food, 8).expect("Should fit");
+
+        assert_eq!(inventory.add(Commodity::Fuel, 3), Ok(2));
+        assert_eq!(inventory.get(&Commodity::Fuel), 2);
+        assert_eq!(
+            inventory.add(Commodity::Fuel, 3),
+            Err(InventoryError::OverCapacity {
+                requested: 3,
+                space_left: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn spill_overflow() {
+        let mut inventory = Inventory::with_capacity_and_overflow(10, OverflowPolicy::Spill);
+        inventory.add(Commodity::Food, 8).expect("Should fit");
+
+        assert_eq!(inventory.add(Commodity::Fuel, 3), Ok(3));
+        assert_eq!(inventory.get(&Commodity::Fuel), 2);
+        assert_eq!(inventory.add(Commodity::Fuel, 3), Ok(3));
+        assert_eq!(inventory.get(&Commodity::Fuel), 2);
+        assert_eq!(inventory.space_left(), 0);
+    }
+
+    #[test]
+    fn bulky_commodities_take_more_space() {
+        let mut inventory = Inventory::with_capacity(10);
+        inventory
+            .add(Commodity::HydrogenTanks, 2)
+            .expect("Should fit");
+
+        assert_eq!(inventory.space_left(), 2);
+        assert_eq!(inventory.room_for(Commodity::HydrogenTanks), 0);
+        assert_eq!(inventory.room_for(Commodity::Fuel), 2);
+        assert_eq!(
+            inventory.add(Commodity::HydrogenTanks, 1),
+            Err(InventoryError::OverCapacity {
+                requested: 1,
+                space_left: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn mass_depends_on_commodity() {
+        let mut inventory = Inventory::with_capacity(10);
+        inventory.add(Commodity::Food, 2).expect("Should fit");
+        inventory.add(Commodity::Fuel, 2).expect("Should fit");
+
+        assert_eq!(inventory.mass(), 6.);
+    }
 }
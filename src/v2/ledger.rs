@@ -0,0 +1,284 @@
+use std::collections::{HashMap, VecDeque};
+use std::ops::Not;
+
+use bevy::prelude::*;
+use uuid::Uuid;
+
+use crate::v2::store::Credits;
+
+pub type AccountId = Uuid;
+
+/// Account that all new money is minted from and burnt money goes back to, its balance is never
+/// tracked
+pub const MINT: AccountId = Uuid::nil();
+
+/// How many of the latest entries the ledger keeps, older ones are only counted in the balances
+const HISTORY_LENGTH: usize = 1000;
+
+#[derive(Component, Debug)]
+pub struct Wallet {
+    pub id: AccountId,
+    credits: Credits,
+}
+
+impl Default for Wallet {
+    fn default() -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            credits: 0,
+        }
+    }
+}
+
+impl Wallet {
+    pub const fn credits(&self) -> Credits {
+        self.credits
+    }
+
+    pub const fn can_afford(&self, amount: Credits) -> bool {
+        self.credits >= amount
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LedgerError {
+    InsufficientFunds {
+        account: AccountId,
+        balance: Credits,
+        required: Credits,
+    },
+    BalanceMismatch {
+        account: AccountId,
+        wallet: Credits,
+        ledger: Credits,
+    },
+    MoneySupplyMismatch {
+        wallets: Credits,
+        minted: Credits,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct LedgerEntry {
+    pub payer: AccountId,
+    pub payee: AccountId,
+    pub amount: Credits,
+}
+
+/// Double-entry record of every credit moved between wallets.
+/// Wallets can only change balance through here so the books always add up
+#[derive(Resource, Default)]
+pub struct Ledger {
+    /// The latest `HISTORY_LENGTH` entries
+    entries: VecDeque<LedgerEntry>,
+    balances: HashMap<AccountId, Credits>,
+    minted: Credits,
+}
+
+impl Ledger {
+    pub fn mint(&mut self, wallet: &mut Wallet, amount: Credits) {
+        wallet.credits += amount;
+        self.minted += amount;
+        *self.balances.entry(wallet.id).or_insert(0) += amount;
+        self.record(LedgerEntry {
+            payer: MINT,
+            payee: wallet.id,
+            amount,
+        });
+    }
+
+    /// Takes money out of circulation, for credits whose owner is gone
+    pub fn burn(&mut self, wallet: &mut Wallet, amount: Credits) -> Result<(), LedgerError> {
+        if wallet.can_afford(amount).not() {
+            return Err(LedgerError::InsufficientFunds {
+                account: wallet.id,
+                balance: wallet.credits,
+                required: amount,
+            });
+        }
+
+        wallet.credits -= amount;
+        self.minted -= amount;
+        *self.balances.entry(wallet.id).or_insert(0) -= amount;
+        self.record(LedgerEntry {
+            payer: wallet.id,
+            payee: MINT,
+            amount,
+        });
+        Ok(())
+    }
+
+    pub fn transfer(
+        &mut self,
+        payer: &mut Wallet,
+        payee: &mut Wallet,
+        amount: Credits,
+    ) -> Result<(), LedgerError> {
+        if payer.can_afford(amount).not() {
+            return Err(LedgerError::InsufficientFunds {
+                account: payer.id,
+                balance: payer.credits,
+                required: amount,
+            });
+        }
+
+        payer.credits -= amount;
+        payee.credits += amount;
+        *self.balances.entry(payer.id).or_insert(0) -= amount;
+        *self.balances.entry(payee.id).or_insert(0) += amount;
+        self.record(LedgerEntry {
+            payer: payer.id,
+            payee: payee.id,
+            amount,
+        });
+        Ok(())
+    }
+
+    pub fn balance(&self, account: &AccountId) -> Credits {
+        *self.balances.get(account).unwrap_or(&0)
+    }
+
+    pub const fn money_supply(&self) -> Credits {
+        self.minted
+    }
+
+    fn record(&mut self, entry: LedgerEntry) {
+        if self.entries.len() == HISTORY_LENGTH {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// The latest entries, oldest first
+    pub fn entries(&self) -> impl ExactSizeIterator<Item = &LedgerEntry> {
+        self.entries.iter()
+    }
+
+    /// Checks that every wallet agrees with the books and that no money appeared out of nowhere
+    pub fn audit<'a>(
+        &self,
+        wallets: impl IntoIterator<Item = &'a Wallet>,
+    ) -> Result<(), LedgerError> {
+        let mut total = 0;
+        for wallet in wallets {
+            let ledger = self.balance(&wallet.id);
+            if wallet.credits != ledger {
+                return Err(LedgerError::BalanceMismatch {
+                    account: wallet.id,
+                    wallet: wallet.credits,
+                    ledger,
+                });
+            }
+            total += wallet.credits;
+        }
+        if total != self.minted {
+            return Err(LedgerError::MoneySupplyMismatch {
+                wallets: total,
+                minted: self.minted,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minted_money_ends_up_in_wallet() {
+        let mut ledger = Ledger::default();
+        let mut wallet = Wallet::default();
+
+        ledger.mint(&mut wallet, 100);
+
+        assert_eq!(wallet.credits(), 100);
+        assert_eq!(ledger.balance(&wallet.id), 100);
+        assert_eq!(ledger.money_supply(), 100);
+        assert_eq!(ledger.audit([&wallet]), Ok(()));
+    }
+
+    #[test]
+    fn transfer_moves_money_between_wallets() {
+        let mut ledger = Ledger::default();
+        let mut alice = Wallet::default();
+        let mut bob = Wallet::default();
+        ledger.mint(&mut alice, 100);
+
+        ledger
+            .transfer(&mut alice, &mut bob, 30)
+            .expect("Alice can afford this");
+
+        assert_eq!(alice.credits(), 70);
+        assert_eq!(bob.credits(), 30);
+        assert_eq!(ledger.entries().len(), 2);
+        assert_eq!(ledger.audit([&alice, &bob]), Ok(()));
+    }
+
+    #[test]
+    fn only_the_latest_entries_are_kept() {
+        let mut ledger = Ledger::default();
+        let mut wallet = Wallet::default();
+
+        for amount in 0..HISTORY_LENGTH as Credits + 10 {
+            ledger.mint(&mut wallet, amount);
+        }
+
+        assert_eq!(ledger.entries().len(), HISTORY_LENGTH);
+        assert_eq!(ledger.entries().next().map(|entry| entry.amount), Some(10));
+        assert_eq!(ledger.audit([&wallet]), Ok(()));
+    }
+
+    #[test]
+    fn burnt_money_leaves_the_money_supply() {
+        let mut ledger = Ledger::default();
+        let mut wallet = Wallet::default();
+        ledger.mint(&mut wallet, 100);
+
+        ledger.burn(&mut wallet, 30).expect("Wallet has the money");
+
+        assert_eq!(wallet.credits(), 70);
+        assert_eq!(ledger.money_supply(), 70);
+        assert_eq!(ledger.audit([&wallet]), Ok(()));
+        assert!(ledger.burn(&mut wallet, 71).is_err());
+    }
+
+    #[test]
+    fn cant_transfer_more_than_you_have() {
+        let mut ledger = Ledger::default();
+        let mut alice = Wallet::default();
+        let mut bob = Wallet::default();
+        ledger.mint(&mut alice, 10);
+
+        let result = ledger.transfer(&mut alice, &mut bob, 11);
+
+        assert_eq!(
+            result,
+            Err(LedgerError::InsufficientFunds {
+                account: alice.id,
+                balance: 10,
+                required: 11,
+            })
+        );
+        assert_eq!(alice.credits(), 10);
+        assert_eq!(bob.credits(), 0);
+        assert_eq!(ledger.audit([&alice, &bob]), Ok(()));
+    }
+
+    #[test]
+    fn audit_notices_missing_wallets() {
+        let mut ledger = Ledger::default();
+        let mut alice = Wallet::default();
+        let mut bob = Wallet::default();
+        ledger.mint(&mut alice, 10);
+        ledger.mint(&mut bob, 10);
+
+        assert_eq!(
+            ledger.audit([&alice]),
+            Err(LedgerError::MoneySupplyMismatch {
+                wallets: 10,
+                minted: 20,
+            })
+        );
+    }
+}
@@ -1,53 +1,57 @@
-use uuid::Uuid;
+use bevy::prelude::*;
 
 use crate::v2::commodity::Commodity;
-use crate::v2::store::{Credits, Store};
+use crate::v2::inventory::Amount;
+use crate::v2::store::{Credits, Store, StoreListing};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CommodityListing {
-    pub store: Uuid,
+    pub planet: Entity,
+    pub position: Vec2,
     pub commodity: Commodity,
+    pub amount: Amount,
     pub price: Credits,
 }
 
-#[derive(Default)]
+/// Every store's quotes, rebuilt each tick so systems don't have to query all stores themselves
+#[derive(Resource, Default)]
 pub struct Market {
-    stores: Vec<Store>,
+    sellers: Vec<CommodityListing>,
+    buyers: Vec<CommodityListing>,
 }
 
 impl Market {
-    pub fn add_store(&mut self, store: Store) {
-        self.stores.push(store);
+    pub fn clear(&mut self) {
+        self.sellers.clear();
+        self.buyers.clear();
     }
 
-    pub fn get_sellers(&self, commodity: Commodity) -> Vec<CommodityListing> {
-        self.stores
+    pub fn add_store(&mut self, planet: Entity, position: Vec2, store: &Store) {
+        let listing = |listing: StoreListing| CommodityListing {
+            planet,
+            position,
+            commodity: listing.commodity,
+            amount: listing.amount,
+            price: listing.price,
+        };
+        self.sellers
+            .extend(store.price_check_buy_from_store().into_iter().map(listing));
+        self.buyers
+            .extend(store.price_check_sell_to_store().into_iter().map(listing));
+    }
+
+    /// Stores that sell `commodity`
+    pub fn get_sellers(&self, commodity: Commodity) -> impl Iterator<Item = &CommodityListing> {
+        self.sellers
             .iter()
-            .filter_map(|store| {
-                store
-                    .price_check_buy_specific_from_store(commodity)
-                    .map(|price| CommodityListing {
-                        store: store.id,
-                        commodity,
-                        price,
-                    })
-            })
-            .collect()
+            .filter(move |listing| listing.commodity == commodity)
     }
 
-    pub fn get_buyers(&self, commodity: Commodity) -> Vec<CommodityListing> {
-        self.stores
+    /// Stores that buy `commodity`
+    pub fn get_buyers(&self, commodity: Commodity) -> impl Iterator<Item = &CommodityListing> {
+        self.buyers
             .iter()
-            .filter_map(|store| {
-                store
-                    .price_check_sell_specific_to_store(&commodity)
-                    .map(|price| CommodityListing {
-                        store: store.id,
-                        commodity,
-                        price,
-                    })
-            })
-            .collect()
+            .filter(move |listing| listing.commodity == commodity)
     }
 }
 
@@ -57,39 +61,48 @@ mod tests {
 
     #[test]
     fn find_a_profitable_trade() {
+        let farm = Entity::from_raw(1);
+        let city = Entity::from_raw(2);
+        let mut farm_store = Store::default();
+        farm_store
+            .give(Commodity::Food, 400)
+            .expect("Farm has room for food");
+        let mut city_store = Store::default();
+        city_store.take(Commodity::Food, 90);
+
         let mut market = Market::default();
-        market.add_store(Store {
-            magically_produces_food: false,
-            ..Store::default()
-        });
-        market.add_store(Store {
-            magically_produces_food: true,
-            ..Store::default()
-        });
+        market.add_store(farm, Vec2::new(-100., 0.), &farm_store);
+        market.add_store(city, Vec2::new(100., 0.), &city_store);
 
         let cheapest_place_to_buy_food = market
             .get_sellers(Commodity::Food)
-            .into_iter()
             .min_by_key(|listing| listing.price)
             .expect("Should be one store here");
 
         let expensivest_place_to_sell_food = market
             .get_buyers(Commodity::Food)
-            .into_iter()
             .max_by_key(|listing| listing.price)
             .expect("Should be one store here");
 
-        assert_ne!(
-            cheapest_place_to_buy_food.store,
-            expensivest_place_to_sell_food.store
-        );
+        assert_eq!(cheapest_place_to_buy_food.planet, farm);
+        assert_eq!(cheapest_place_to_buy_food.position, Vec2::new(-100., 0.));
+        assert_eq!(expensivest_place_to_sell_food.planet, city);
         assert!(
             cheapest_place_to_buy_food.price < expensivest_place_to_sell_food.price,
             "{:?} should be cheaper than {:?}",
             cheapest_place_to_buy_food,
             expensivest_place_to_sell_food
         );
-        let profit = expensivest_place_to_sell_food.price - cheapest_place_to_buy_food.price;
-        assert!(profit > 0);
+    }
+
+    #[test]
+    fn clearing_forgets_all_stores() {
+        let mut market = Market::default();
+        market.add_store(Entity::from_raw(1), Vec2::ZERO, &Store::default());
+
+        market.clear();
+
+        assert_eq!(market.get_sellers(Commodity::Food).count(), 0);
+        assert_eq!(market.get_buyers(Commodity::Food).count(), 0);
     }
 }
@@ -1,4 +1,16 @@
 pub mod commodity;
+pub mod demand;
+pub mod deposit;
+pub mod docking;
+pub mod fuel;
 pub mod inventory;
-// pub mod market;
+pub mod ledger;
+pub mod market;
+pub mod movement;
+pub mod orbit;
+pub mod order_book;
+pub mod planner;
+pub mod population;
+pub mod pricing;
+pub mod production;
 pub mod store;
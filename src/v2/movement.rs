@@ -0,0 +1,196 @@
+use bevy::prelude::*;
+
+/// Slower than this counts as standing still
+const AT_REST: f32 = 1.;
+
+/// How a ship is moving right now
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct Movement {
+    pub velocity: Vec2,
+    /// Radians, counter-clockwise from the x-axis
+    pub heading: f32,
+}
+
+/// What a ship's engines can do
+#[derive(Debug, Clone, Copy)]
+pub struct Handling {
+    pub max_speed: f32,
+    pub max_acceleration: f32,
+    /// Radians per second
+    pub turn_rate: f32,
+}
+
+impl Movement {
+    pub fn speed(&self) -> f32 {
+        self.velocity.length()
+    }
+
+    pub fn is_at_rest(&self) -> bool {
+        self.speed() < AT_REST
+    }
+
+    /// Turns towards `target` and accelerates, braking in time to come to rest on top of it.
+    /// Returns how far the ship moved
+    pub fn steer_towards(
+        &mut self,
+        position: Vec2,
+        target: Vec2,
+        handling: &Handling,
+        delta_seconds: f32,
+    ) -> Vec2 {
+        let to_target = target - position;
+        let distance = to_target.length();
+        if delta_seconds <= 0. {
+            return Vec2::ZERO;
+        }
+
+        let desired_velocity = if distance > f32::EPSILON {
+            self.turn_towards(to_target, handling.turn_rate * delta_seconds);
+            let facing = Vec2::from_angle(self.heading);
+            // fastest speed the ship can still stop from within the remaining distance, allowing
+            // for one more frame at that speed before the brakes kick in
+            let acceleration = handling.max_acceleration;
+            let braking_speed = acceleration
+                * ((delta_seconds * delta_seconds + 2. * distance / acceleration).sqrt()
+                    - delta_seconds);
+            let speed = handling
+                .max_speed
+                .min(braking_speed)
+                .min(distance / delta_seconds);
+            // don't race off while still facing the wrong way
+            let alignment = facing.dot(to_target / distance).max(0.);
+            facing * speed * alignment
+        } else {
+            Vec2::ZERO
+        };
+
+        let change = (desired_velocity - self.velocity)
+            .clamp_length_max(handling.max_acceleration * delta_seconds);
+        self.velocity = (self.velocity + change).clamp_length_max(handling.max_speed);
+        self.velocity * delta_seconds
+    }
+
+    /// Comes to a stop as quickly as the engines allow, returns how far the ship moved
+    pub fn brake(&mut self, handling: &Handling, delta_seconds: f32) -> Vec2 {
+        let change = (-self.velocity).clamp_length_max(handling.max_acceleration * delta_seconds);
+        self.velocity += change;
+        self.velocity * delta_seconds
+    }
+
+    fn turn_towards(&mut self, direction: Vec2, max_turn: f32) {
+        let wanted = direction.y.atan2(direction.x);
+        let difference = wrap_angle(wanted - self.heading);
+        self.heading = wrap_angle(self.heading + difference.clamp(-max_turn, max_turn));
+    }
+}
+
+/// Keeps an angle within -π..π
+fn wrap_angle(angle: f32) -> f32 {
+    let wrapped = (angle + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU);
+    wrapped - std::f32::consts::PI
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    use super::*;
+
+    const FRAME: f32 = 1. / 60.;
+
+    fn handling() -> Handling {
+        Handling {
+            max_speed: 300.,
+            max_acceleration: 200.,
+            turn_rate: PI,
+        }
+    }
+
+    /// Flies until at rest on the target, returns how many seconds it took and the furthest past
+    /// the target the ship ever got, measured along the line from start to target
+    fn fly(start: Vec2, target: Vec2, mut movement: Movement) -> (f32, f32, Vec2) {
+        let direction = (target - start).normalize();
+        let mut position = start;
+        let mut overshoot: f32 = 0.;
+        let mut seconds = 0.;
+        while seconds < 60. {
+            position += movement.steer_towards(position, target, &handling(), FRAME);
+            overshoot = overshoot.max((position - target).dot(direction));
+            seconds += FRAME;
+            if position.distance(target) < 1. && movement.is_at_rest() {
+                break;
+            }
+        }
+        (seconds, overshoot, position)
+    }
+
+    #[test]
+    fn arrives_without_overshooting() {
+        let target = Vec2::new(1000., 0.);
+
+        let (seconds, overshoot, position) = fly(Vec2::ZERO, target, Movement::default());
+
+        assert!(seconds < 60., "Never arrived, ended up at {}", position);
+        assert!(overshoot < 0.5, "Overshot by {}", overshoot);
+    }
+
+    #[test]
+    fn short_hops_dont_overshoot_either() {
+        let target = Vec2::new(15., 10.);
+        let movement = Movement {
+            heading: (10_f32).atan2(15.),
+            ..default()
+        };
+
+        let (seconds, overshoot, position) = fly(Vec2::ZERO, target, movement);
+
+        assert!(seconds < 60., "Never arrived, ended up at {}", position);
+        assert!(overshoot < 0.5, "Overshot by {}", overshoot);
+    }
+
+    #[test]
+    fn turns_around_to_arrive_when_already_moving_away() {
+        let target = Vec2::new(500., 0.);
+        let movement = Movement {
+            velocity: Vec2::new(-300., 0.),
+            heading: PI,
+        };
+
+        let (seconds, _, position) = fly(Vec2::ZERO, target, movement);
+
+        assert!(seconds < 60., "Never arrived, ended up at {}", position);
+    }
+
+    #[test]
+    fn never_faster_than_top_speed() {
+        let mut movement = Movement::default();
+        let mut position = Vec2::ZERO;
+        for _ in 0..600 {
+            position +=
+                movement.steer_towards(position, Vec2::new(10_000., 0.), &handling(), FRAME);
+            assert!(movement.speed() <= handling().max_speed + 0.01);
+        }
+    }
+
+    #[test]
+    fn turning_is_limited_by_turn_rate() {
+        let mut movement = Movement::default();
+
+        movement.steer_towards(Vec2::ZERO, Vec2::new(0., 100.), &handling(), 0.1);
+
+        assert!((movement.heading - PI * 0.1).abs() < 0.001);
+        assert!(movement.heading < FRAC_PI_2);
+    }
+
+    #[test]
+    fn brakes_to_a_stop() {
+        let mut movement = Movement {
+            velocity: Vec2::new(100., 0.),
+            heading: 0.,
+        };
+        for _ in 0..60 {
+            movement.brake(&handling(), FRAME);
+        }
+        assert!(movement.is_at_rest());
+    }
+}
@@ -0,0 +1,114 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+/// Most rounds of guessing where a moving target will be before giving up on it
+const INTERCEPT_ITERATIONS: usize = 20;
+/// Seconds two guesses can be apart and still count as agreeing
+const INTERCEPT_TOLERANCE: f32 = 0.01;
+
+/// A circular orbit around the star, or around another orbiting body
+#[derive(Debug, Clone, Copy, Component)]
+pub struct Orbit {
+    /// `None` for the star at the origin
+    pub around: Option<Entity>,
+    pub radius: f32,
+    /// Seconds per revolution
+    pub period: f32,
+    /// Radians, where along the orbit the body is right now
+    pub angle: f32,
+}
+
+impl Orbit {
+    pub fn advance(&mut self, delta_seconds: f32) {
+        self.angle = (self.angle + TAU * delta_seconds / self.period).rem_euclid(TAU);
+    }
+
+    /// Where the body is relative to what it orbits, `seconds` from now
+    pub fn offset_in(&self, seconds: f32) -> Vec2 {
+        Vec2::from_angle(self.angle + TAU * seconds / self.period) * self.radius
+    }
+}
+
+/// Where to head to meet a moving target, and how many seconds it takes to get there.
+/// `target_in` is where the target is a number of seconds from now, `travel_time` how long it takes
+/// to cover a distance. Targets too quick to pin down are aimed at where they are now
+pub fn intercept(
+    from: Vec2,
+    target_in: impl Fn(f32) -> Vec2,
+    travel_time: impl Fn(f32) -> f32,
+) -> (Vec2, f32) {
+    let mut seconds = 0.;
+    for _ in 0..INTERCEPT_ITERATIONS {
+        let guess = travel_time(from.distance(target_in(seconds)));
+        if (guess - seconds).abs() < INTERCEPT_TOLERANCE {
+            return (target_in(guess), guess);
+        }
+        seconds = guess;
+    }
+    let now = target_in(0.);
+    (now, travel_time(from.distance(now)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn orbit() -> Orbit {
+        Orbit {
+            around: None,
+            radius: 100.,
+            period: 40.,
+            angle: 0.,
+        }
+    }
+
+    #[test]
+    fn comes_back_around_after_a_period() {
+        let mut orbit = orbit();
+        let start = orbit.offset_in(0.);
+
+        assert!(orbit.offset_in(40.).distance(start) < 0.01);
+        assert!(orbit.offset_in(20.).distance(-start) < 0.01);
+
+        orbit.advance(10.);
+        assert!(orbit.offset_in(0.).distance(Vec2::new(0., 100.)) < 0.01);
+        assert!(orbit.offset_in(30.).distance(start) < 0.01);
+    }
+
+    #[test]
+    fn stationary_targets_are_aimed_at_directly() {
+        let target = Vec2::new(300., 400.);
+
+        let (aim, seconds) = intercept(Vec2::ZERO, |_| target, |distance| distance / 100.);
+
+        assert_eq!(aim, target);
+        assert_eq!(seconds, 5.);
+    }
+
+    #[test]
+    fn leads_a_moving_target() {
+        let orbit = orbit();
+        let from = Vec2::new(-500., 0.);
+        let travel_time = |distance: f32| distance / 200.;
+
+        let (aim, seconds) = intercept(from, |seconds| orbit.offset_in(seconds), travel_time);
+
+        // the ship and the planet turn up at the same place at the same time
+        assert!(aim.distance(orbit.offset_in(seconds)) < 0.01);
+        assert!((travel_time(from.distance(aim)) - seconds).abs() < 0.1);
+        assert!(aim.distance(orbit.offset_in(0.)) > 1.);
+    }
+
+    #[test]
+    fn aims_at_targets_faster_than_the_ship_where_they_are_now() {
+        // running away at twice the ship's speed
+        let target_in = |seconds: f32| Vec2::new(100. + 100. * seconds, 0.);
+        let travel_time = |distance: f32| distance / 50.;
+
+        let (aim, seconds) = intercept(Vec2::ZERO, target_in, travel_time);
+
+        assert_eq!(aim, Vec2::new(100., 0.));
+        assert_eq!(seconds, 2.);
+    }
+}
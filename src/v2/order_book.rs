@@ -0,0 +1,333 @@
+use std::cmp::Reverse;
+
+use bevy::prelude::*;
+use strum::IntoEnumIterator;
+use uuid::Uuid;
+
+use crate::v2::commodity::Commodity;
+use crate::v2::inventory::Amount;
+use crate::v2::ledger::{Ledger, LedgerError, Wallet};
+use crate::v2::store::{Credits, Receipt};
+
+pub type OrderId = Uuid;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Clone)]
+pub struct LimitOrder {
+    pub id: OrderId,
+    /// Entity whose wallet pays or gets paid
+    pub trader: Entity,
+    pub side: Side,
+    pub commodity: Commodity,
+    /// What's left to fill
+    pub amount: Amount,
+    /// Most a buyer pays or least a seller accepts, per unit
+    pub price: Credits,
+    /// Elapsed game time in seconds after which the order is dropped
+    pub expires_at: f32,
+    /// Orders posted earlier get filled first at the same price
+    sequence: u64,
+}
+
+#[derive(Debug)]
+pub struct Trade {
+    pub buyer: Entity,
+    pub seller: Entity,
+    /// What the buyer escrowed per unit, anything above the trade price is refunded
+    pub bid_price: Credits,
+    pub receipt: Receipt,
+}
+
+/// Limit orders posted at one planet, matched against each other every economy tick.
+/// Buyers pay into escrow when posting and sellers hand over their goods,
+/// so a match can always be settled
+#[derive(Component, Default)]
+pub struct OrderBook {
+    orders: Vec<LimitOrder>,
+    next_sequence: u64,
+    pub escrow: Wallet,
+}
+
+impl OrderBook {
+    #[allow(clippy::too_many_arguments)]
+    pub fn post_buy(
+        &mut self,
+        trader: Entity,
+        commodity: Commodity,
+        amount: Amount,
+        price: Credits,
+        expires_at: f32,
+        buyer: &mut Wallet,
+        ledger: &mut Ledger,
+    ) -> Result<OrderId, LedgerError> {
+        ledger.transfer(buyer, &mut self.escrow, amount * price)?;
+        Ok(self.post(trader, Side::Buy, commodity, amount, price, expires_at))
+    }
+
+    /// The goods have to have been taken from the seller already, they're held by the order
+    pub fn post_sell(
+        &mut self,
+        trader: Entity,
+        commodity: Commodity,
+        amount: Amount,
+        price: Credits,
+        expires_at: f32,
+    ) -> OrderId {
+        self.post(trader, Side::Sell, commodity, amount, price, expires_at)
+    }
+
+    fn post(
+        &mut self,
+        trader: Entity,
+        side: Side,
+        commodity: Commodity,
+        amount: Amount,
+        price: Credits,
+        expires_at: f32,
+    ) -> OrderId {
+        let id = Uuid::new_v4();
+        self.orders.push(LimitOrder {
+            id,
+            trader,
+            side,
+            commodity,
+            amount,
+            price,
+            expires_at,
+            sequence: self.next_sequence,
+        });
+        self.next_sequence += 1;
+        id
+    }
+
+    pub fn orders(&self) -> &[LimitOrder] {
+        &self.orders
+    }
+
+    pub fn has_orders_from(&self, trader: Entity) -> bool {
+        self.orders.iter().any(|order| order.trader == trader)
+    }
+
+    pub fn best_bid(&self, commodity: Commodity) -> Option<Credits> {
+        self.orders
+            .iter()
+            .filter(|order| order.side == Side::Buy && order.commodity == commodity)
+            .map(|order| order.price)
+            .max()
+    }
+
+    pub fn best_ask(&self, commodity: Commodity) -> Option<Credits> {
+        self.orders
+            .iter()
+            .filter(|order| order.side == Side::Sell && order.commodity == commodity)
+            .map(|order| order.price)
+            .min()
+    }
+
+    /// Fills crossing orders, highest bid against lowest ask, at the price of whichever was posted first
+    pub fn match_orders(&mut self) -> Vec<Trade> {
+        let mut trades = vec![];
+        for commodity in Commodity::iter() {
+            while let Some(trade) = self.match_best(commodity) {
+                trades.push(trade);
+            }
+        }
+        trades
+    }
+
+    /// The best bid that crosses an ask from someone else, traders don't trade with themselves
+    fn match_best(&mut self, commodity: Commodity) -> Option<Trade> {
+        let mut bids: Vec<_> = self
+            .orders
+            .iter()
+            .enumerate()
+            .filter(|(_, order)| order.side == Side::Buy && order.commodity == commodity)
+            .collect();
+        bids.sort_by_key(|(_, order)| (Reverse(order.price), order.sequence));
+        let (bid_index, ask_index) = bids.into_iter().find_map(|(bid_index, bid)| {
+            let (ask_index, ask) = self
+                .orders
+                .iter()
+                .enumerate()
+                .filter(|(_, order)| order.side == Side::Sell && order.commodity == commodity)
+                .filter(|(_, order)| order.trader != bid.trader)
+                .min_by_key(|(_, order)| (order.price, order.sequence))?;
+            (bid.price >= ask.price).then_some((bid_index, ask_index))
+        })?;
+        let (bid, ask) = (&self.orders[bid_index], &self.orders[ask_index]);
+
+        let amount = bid.amount.min(ask.amount);
+        let price = if bid.sequence < ask.sequence {
+            bid.price
+        } else {
+            ask.price
+        };
+        let trade = Trade {
+            buyer: bid.trader,
+            seller: ask.trader,
+            bid_price: bid.price,
+            receipt: Receipt {
+                commodity,
+                amount,
+                unit_price: price,
+                total: price * amount,
+            },
+        };
+        self.orders[bid_index].amount -= amount;
+        self.orders[ask_index].amount -= amount;
+        self.orders.retain(|order| order.amount > 0);
+        Some(trade)
+    }
+
+    /// Removes and returns orders that have run out of time, whatever they hold is up to the caller
+    pub fn remove_expired(&mut self, now: f32) -> Vec<LimitOrder> {
+        let (expired, open) = self
+            .orders
+            .drain(..)
+            .partition(|order| order.expires_at <= now);
+        self.orders = open;
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book_with_money() -> (OrderBook, Wallet, Ledger) {
+        let mut ledger = Ledger::default();
+        let mut wallet = Wallet::default();
+        ledger.mint(&mut wallet, 1000);
+        (OrderBook::default(), wallet, ledger)
+    }
+
+    #[test]
+    fn posting_a_buy_order_escrows_the_money() {
+        let (mut book, mut wallet, mut ledger) = book_with_money();
+
+        book.post_buy(
+            Entity::from_raw(1),
+            Commodity::Food,
+            10,
+            5,
+            10.,
+            &mut wallet,
+            &mut ledger,
+        )
+        .expect("Can afford it");
+
+        assert_eq!(wallet.credits(), 950);
+        assert_eq!(book.escrow.credits(), 50);
+        assert!(book.has_orders_from(Entity::from_raw(1)));
+        assert_eq!(book.best_bid(Commodity::Food), Some(5));
+    }
+
+    #[test]
+    fn crossing_orders_trade_at_the_resting_price() {
+        let (mut book, mut wallet, mut ledger) = book_with_money();
+        let buyer = Entity::from_raw(1);
+        let seller = Entity::from_raw(2);
+        book.post_buy(buyer, Commodity::Food, 10, 6, 10., &mut wallet, &mut ledger)
+            .expect("Can afford it");
+        book.post_sell(seller, Commodity::Food, 4, 3, 10.);
+
+        let trades = book.match_orders();
+
+        assert_eq!(trades.len(), 1);
+        let trade = &trades[0];
+        assert_eq!(trade.buyer, buyer);
+        assert_eq!(trade.seller, seller);
+        assert_eq!(trade.bid_price, 6);
+        assert_eq!(trade.receipt.amount, 4);
+        assert_eq!(trade.receipt.unit_price, 6);
+        assert_eq!(book.orders().len(), 1);
+        assert_eq!(book.orders()[0].amount, 6);
+    }
+
+    #[test]
+    fn cheapest_seller_gets_filled_first() {
+        let (mut book, mut wallet, mut ledger) = book_with_money();
+        let pricey = Entity::from_raw(2);
+        let cheap = Entity::from_raw(3);
+        book.post_sell(pricey, Commodity::Fuel, 5, 8, 10.);
+        book.post_sell(cheap, Commodity::Fuel, 5, 7, 10.);
+        book.post_buy(
+            Entity::from_raw(1),
+            Commodity::Fuel,
+            7,
+            9,
+            10.,
+            &mut wallet,
+            &mut ledger,
+        )
+        .expect("Can afford it");
+
+        let trades = book.match_orders();
+
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].seller, cheap);
+        assert_eq!(trades[0].receipt.unit_price, 7);
+        assert_eq!(trades[0].receipt.amount, 5);
+        assert_eq!(trades[1].seller, pricey);
+        assert_eq!(trades[1].receipt.unit_price, 8);
+        assert_eq!(trades[1].receipt.amount, 2);
+    }
+
+    #[test]
+    fn orders_that_dont_cross_stay_open() {
+        let (mut book, mut wallet, mut ledger) = book_with_money();
+        book.post_buy(
+            Entity::from_raw(1),
+            Commodity::Food,
+            10,
+            2,
+            10.,
+            &mut wallet,
+            &mut ledger,
+        )
+        .expect("Can afford it");
+        book.post_sell(Entity::from_raw(2), Commodity::Food, 10, 3, 10.);
+        book.post_sell(Entity::from_raw(2), Commodity::Fuel, 10, 1, 10.);
+
+        assert!(book.match_orders().is_empty());
+        assert_eq!(book.orders().len(), 3);
+    }
+
+    #[test]
+    fn expired_orders_are_removed() {
+        let mut book = OrderBook::default();
+        book.post_sell(Entity::from_raw(1), Commodity::Food, 10, 3, 5.);
+        book.post_sell(Entity::from_raw(2), Commodity::Food, 10, 3, 15.);
+
+        let expired = book.remove_expired(10.);
+
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].trader, Entity::from_raw(1));
+        assert_eq!(book.orders().len(), 1);
+    }
+
+    #[test]
+    fn traders_dont_trade_with_themselves() {
+        let (mut book, mut wallet, mut ledger) = book_with_money();
+        let planet = Entity::from_raw(1);
+        let ship = Entity::from_raw(2);
+        book.post_buy(planet, Commodity::Food, 5, 6, 10., &mut wallet, &mut ledger)
+            .expect("Can afford it");
+        book.post_sell(planet, Commodity::Food, 5, 3, 10.);
+
+        assert!(book.match_orders().is_empty());
+
+        book.post_buy(ship, Commodity::Food, 5, 4, 10., &mut wallet, &mut ledger)
+            .expect("Can afford it");
+        let trades = book.match_orders();
+
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].buyer, ship);
+        assert_eq!(trades[0].seller, planet);
+    }
+}
@@ -0,0 +1,681 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use strum::IntoEnumIterator;
+
+use crate::v2::commodity::Commodity;
+use crate::v2::fuel::fuel_needed;
+use crate::v2::inventory::{Amount, Inventory};
+use crate::v2::market::{CommodityListing, Market};
+use crate::v2::orbit::intercept;
+use crate::v2::store::{Credits, Planned, Store};
+
+/// What the planner needs to know about the ship it's planning for
+#[derive(Clone, Copy)]
+pub struct ShipProfile<'a> {
+    pub position: Vec2,
+    /// Top speed
+    pub speed: f32,
+    pub thrust: f32,
+    /// Mass of the ship without any cargo
+    pub hull_mass: f32,
+    pub hold: &'a Inventory,
+    pub budget: Credits,
+    pub constraints: Constraints,
+    /// Fuel in the tank
+    pub fuel: f32,
+    pub tank_capacity: f32,
+    /// What burning a unit of fuel costs, whether it's bought on the way or already in the tank
+    pub fuel_price: f32,
+}
+
+/// Limits on what a ship may trade and where
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Constraints {
+    pub commodity: Option<Commodity>,
+    /// Only trade with planets within this distance of a point
+    pub within: Option<(Vec2, f32)>,
+}
+
+impl Constraints {
+    pub fn allows(&self, listing: &CommodityListing) -> bool {
+        self.commodity
+            .is_none_or(|commodity| commodity == listing.commodity)
+            && self
+                .within
+                .is_none_or(|(center, range)| center.distance(listing.position) <= range)
+    }
+}
+
+impl ShipProfile<'_> {
+    /// Seconds to fly between two points starting and ending at rest, carrying `cargo_mass`
+    pub fn travel_time(&self, from: Vec2, to: Vec2, cargo_mass: f32) -> f32 {
+        let acceleration = self.thrust / (self.hull_mass + cargo_mass);
+        travel_time(from.distance(to), self.speed, acceleration)
+    }
+
+    pub fn fuel_needed(&self, from: Vec2, to: Vec2, cargo_mass: f32) -> f32 {
+        fuel_needed(from.distance(to), self.hull_mass + cargo_mass)
+    }
+}
+
+/// Accelerates up to top speed and cruises, or never reaches top speed on short hops
+pub fn travel_time(distance: f32, top_speed: f32, acceleration: f32) -> f32 {
+    let distance_to_top_speed = top_speed * top_speed / (2. * acceleration);
+    if distance < distance_to_top_speed {
+        (2. * distance / acceleration).sqrt()
+    } else {
+        top_speed / acceleration + (distance - distance_to_top_speed) / top_speed
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TradeRoute {
+    pub store_to_buy_from: Entity,
+    pub store_to_sell_to: Entity,
+    pub commodity: Commodity,
+    pub amount: Amount,
+    /// Total for the whole amount, not per unit
+    pub cost_to_buy_commodity: Credits,
+    /// Total for the whole amount, not per unit
+    pub price_to_sell_commodity: Credits,
+    /// Where the buyer is, and so where the ship ends up
+    pub destination: Vec2,
+    /// Flying to the seller and then on to the buyer
+    pub travel_time: f32,
+    pub fuel_used: f32,
+    pub fuel_cost: Credits,
+    /// Fuel to buy at the seller to make it to the buyer
+    pub refuel: Amount,
+}
+
+impl TradeRoute {
+    pub const fn profit(&self) -> Credits {
+        self.price_to_sell_commodity
+            .saturating_sub(self.cost_to_buy_commodity)
+            .saturating_sub(self.fuel_cost)
+    }
+
+    pub fn profit_per_second(&self) -> f32 {
+        self.profit() as f32 / self.travel_time.max(1.)
+    }
+}
+
+/// A chain of trades flown one after the other, each leg buying where the previous one sold
+#[derive(Debug, Default)]
+pub struct Itinerary {
+    pub legs: Vec<TradeRoute>,
+}
+
+impl Itinerary {
+    /// What the legs so far buy from and sell to each store
+    fn planned(&self) -> HashMap<Entity, Planned> {
+        let mut planned: HashMap<Entity, Planned> = HashMap::new();
+        for leg in &self.legs {
+            let seller = planned.entry(leg.store_to_buy_from).or_default();
+            seller.buy(leg.commodity, leg.amount);
+            seller.buy(Commodity::Fuel, leg.refuel);
+            planned.entry(leg.store_to_sell_to).or_default().sell(
+                leg.commodity,
+                leg.amount,
+                leg.price_to_sell_commodity,
+            );
+        }
+        planned
+    }
+
+    pub fn profit(&self) -> Credits {
+        self.legs.iter().map(TradeRoute::profit).sum()
+    }
+
+    pub fn travel_time(&self) -> f32 {
+        self.legs.iter().map(|leg| leg.travel_time).sum()
+    }
+
+    pub fn profit_per_second(&self) -> f32 {
+        self.profit() as f32 / self.travel_time().max(1.)
+    }
+}
+
+/// Where the planner looks up what stores quote and where planets will be
+struct Surroundings<'f, 's> {
+    market: &'f Market,
+    store: &'f dyn Fn(Entity) -> Option<&'s Store>,
+    position_in: &'f dyn Fn(Entity, f32) -> Option<Vec2>,
+}
+
+impl Surroundings<'_, '_> {
+    /// Where the ship meets the planet behind `listing` if it sets out from `from` `seconds_in`
+    /// from now, and how long it takes to get there
+    fn meet(
+        &self,
+        ship: &ShipProfile,
+        from: Vec2,
+        listing: &CommodityListing,
+        seconds_in: f32,
+        cargo_mass: f32,
+    ) -> (Vec2, f32) {
+        let acceleration = ship.thrust / (ship.hull_mass + cargo_mass);
+        intercept(
+            from,
+            |seconds| {
+                (self.position_in)(listing.planet, seconds_in + seconds).unwrap_or(listing.position)
+            },
+            |distance| travel_time(distance, ship.speed, acceleration),
+        )
+    }
+}
+
+/// Searches chains of up to `max_legs` trades for the one that makes the most money per second,
+/// counting the trip to the first seller and that prices move as the ship buys and sells.
+/// `position_in` is where a planet is a number of seconds from now, `None` for one that stays put
+pub fn plan_itinerary<'s>(
+    market: &Market,
+    store: impl Fn(Entity) -> Option<&'s Store>,
+    position_in: impl Fn(Entity, f32) -> Option<Vec2>,
+    ship: &ShipProfile,
+    max_legs: usize,
+) -> Option<Itinerary> {
+    let surroundings = Surroundings {
+        market,
+        store: &store,
+        position_in: &position_in,
+    };
+    let mut best = None;
+    search(
+        &surroundings,
+        ship,
+        None,
+        max_legs,
+        &mut Itinerary::default(),
+        &mut best,
+    );
+    best
+}
+
+fn search(
+    surroundings: &Surroundings,
+    ship: &ShipProfile,
+    from_planet: Option<Entity>,
+    legs_left: usize,
+    current: &mut Itinerary,
+    best: &mut Option<Itinerary>,
+) {
+    if legs_left == 0 {
+        return;
+    }
+    for leg in candidate_routes(surroundings, ship, from_planet, current) {
+        let next_ship = ShipProfile {
+            position: leg.destination,
+            budget: ship.budget + leg.profit(),
+            fuel: ship.fuel - leg.fuel_used + leg.refuel as f32,
+            ..*ship
+        };
+        let next_planet = leg.store_to_sell_to;
+        current.legs.push(leg);
+        if best
+            .as_ref()
+            .is_none_or(|best| current.profit_per_second() > best.profit_per_second())
+        {
+            *best = Some(Itinerary {
+                legs: current.legs.clone(),
+            });
+        }
+        search(
+            surroundings,
+            &next_ship,
+            Some(next_planet),
+            legs_left - 1,
+            current,
+            best,
+        );
+        current.legs.pop();
+    }
+}
+
+/// Every profitable trade the ship could make after the legs planned so far, optionally only
+/// buying at `from_planet`. Planets will have moved on and stores will have traded with the ship
+/// by the time it sets out
+fn candidate_routes(
+    surroundings: &Surroundings,
+    ship: &ShipProfile,
+    from_planet: Option<Entity>,
+    so_far: &Itinerary,
+) -> Vec<TradeRoute> {
+    let Surroundings { market, store, .. } = surroundings;
+    let seconds_in = so_far.travel_time();
+    let planned = so_far.planned();
+    let nothing_planned = Planned::default();
+    let planned_at = |planet: Entity| planned.get(&planet).unwrap_or(&nothing_planned);
+    Commodity::iter()
+        .flat_map(|commodity| {
+            market
+                .get_sellers(commodity)
+                .filter(move |seller| from_planet.is_none_or(|planet| planet == seller.planet))
+                .filter(|seller| ship.constraints.allows(seller))
+                .flat_map(move |seller| {
+                    market
+                        .get_buyers(commodity)
+                        .filter(move |buyer| buyer.planet != seller.planet)
+                        .filter(|buyer| ship.constraints.allows(buyer))
+                        .map(move |buyer| (commodity, seller, buyer))
+                })
+        })
+        .filter_map(|(commodity, seller, buyer)| {
+            let buy = store(seller.planet)?.quote_buy_after(
+                planned_at(seller.planet),
+                commodity,
+                ship.hold.room_for(commodity),
+                Some(ship.budget),
+            )?;
+            let sell = store(buyer.planet)?.quote_sell_after(
+                planned_at(buyer.planet),
+                commodity,
+                buy.amount,
+            )?;
+            // only count what the buyer can actually take
+            let cost = if sell.amount < buy.amount {
+                store(seller.planet)?
+                    .quote_buy_after(planned_at(seller.planet), commodity, sell.amount, None)?
+                    .total
+            } else {
+                buy.total
+            };
+            let cargo_mass = ship.hold.mass() + commodity.mass() * sell.amount as f32;
+            let (at_seller, to_seller_time) =
+                surroundings.meet(ship, ship.position, seller, seconds_in, ship.hold.mass());
+            let (at_buyer, to_buyer_time) = surroundings.meet(
+                ship,
+                at_seller,
+                buyer,
+                seconds_in + to_seller_time,
+                cargo_mass,
+            );
+            let travel_time = to_seller_time + to_buyer_time;
+
+            // tops up at the seller if the tank won't last all the way to the buyer
+            let to_seller = ship.fuel_needed(ship.position, at_seller, ship.hold.mass());
+            let to_buyer = ship.fuel_needed(at_seller, at_buyer, cargo_mass);
+            let fuel_at_seller = ship.fuel - to_seller;
+            if fuel_at_seller < 0. {
+                return None;
+            }
+            let refuel = (to_buyer - fuel_at_seller).max(0.).ceil() as Amount;
+            if fuel_at_seller + refuel as f32 > ship.tank_capacity {
+                return None;
+            }
+            let refuel_cost = if refuel > 0 {
+                let mut planned = planned_at(seller.planet).clone();
+                planned.buy(commodity, sell.amount);
+                store(seller.planet)?
+                    .quote_buy_after(&planned, Commodity::Fuel, refuel, None)
+                    .filter(|quote| quote.amount >= refuel)?
+                    .total
+            } else {
+                0
+            };
+            if cost + refuel_cost > ship.budget {
+                return None;
+            }
+            let fuel_used = to_seller + to_buyer;
+            let fuel_cost = (fuel_used * ship.fuel_price).ceil() as Credits;
+
+            (cost + fuel_cost < sell.total).then_some(TradeRoute {
+                store_to_buy_from: seller.planet,
+                store_to_sell_to: buyer.planet,
+                commodity,
+                amount: sell.amount,
+                cost_to_buy_commodity: cost,
+                price_to_sell_commodity: sell.total,
+                destination: at_buyer,
+                travel_time,
+                fuel_used,
+                fuel_cost,
+                refuel,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::v2::ledger::Ledger;
+
+    use super::*;
+
+    fn ship(position: Vec2, hold: &Inventory) -> ShipProfile<'_> {
+        ShipProfile {
+            position,
+            speed: 100.,
+            thrust: 1000.,
+            hull_mass: 10.,
+            hold,
+            budget: 1000,
+            constraints: Constraints::default(),
+            fuel: 100.,
+            tank_capacity: 100.,
+            fuel_price: 0.,
+        }
+    }
+
+    /// Planets that stay where they're listed
+    fn stationary(_: Entity, _: f32) -> Option<Vec2> {
+        None
+    }
+
+    fn store_with_food(food: Amount) -> Store {
+        let mut store = Store::default();
+        store.take(Commodity::Food, 100);
+        if food > 0 {
+            store.give(Commodity::Food, food).expect("Should fit");
+        }
+        store
+    }
+
+    /// Stores with money to trade, listed on a market where they are
+    struct Planets {
+        stores: Vec<(Entity, Store)>,
+        market: Market,
+    }
+
+    impl Planets {
+        fn new(planets: impl IntoIterator<Item = (Entity, Vec2, Store)>) -> Self {
+            let mut ledger = Ledger::default();
+            let mut market = Market::default();
+            let mut stores = vec![];
+            for (planet, position, mut store) in planets {
+                ledger.mint(&mut store.wallet, 1000);
+                market.add_store(planet, position, &store);
+                stores.push((planet, store));
+            }
+            Self { stores, market }
+        }
+
+        fn lookup<'a>(&'a self) -> impl Fn(Entity) -> Option<&'a Store> + Copy + 'a {
+            move |planet| {
+                self.stores
+                    .iter()
+                    .find(|(entity, _)| *entity == planet)
+                    .map(|(_, store)| store)
+            }
+        }
+    }
+
+    #[test]
+    fn short_hops_never_reach_top_speed() {
+        // 100 units/s² gets to 100 units/s after 50 units
+        assert_eq!(travel_time(50., 100., 100.), 1.);
+        assert_eq!(travel_time(150., 100., 100.), 2.);
+        assert!(travel_time(25., 100., 100.) < 1.);
+    }
+
+    #[test]
+    fn heavy_cargo_is_slower() {
+        let hold = Inventory::with_capacity(20);
+        let ship = ship(Vec2::ZERO, &hold);
+
+        let empty = ship.travel_time(Vec2::ZERO, Vec2::new(500., 0.), 0.);
+        let full = ship.travel_time(Vec2::ZERO, Vec2::new(500., 0.), 40.);
+
+        assert!(full > empty, "{} vs {}", full, empty);
+    }
+
+    #[test]
+    fn prefers_nearby_route_when_it_pays_more_per_second() {
+        let farm = Entity::from_raw(1);
+        let near_city = Entity::from_raw(2);
+        let far_city = Entity::from_raw(3);
+        let planets = Planets::new([
+            (farm, Vec2::ZERO, store_with_food(400)),
+            (near_city, Vec2::new(100., 0.), store_with_food(10)),
+            (far_city, Vec2::new(5000., 0.), store_with_food(0)),
+        ]);
+        let (market, lookup) = (&planets.market, planets.lookup());
+        let hold = Inventory::with_capacity(10);
+
+        let itinerary = plan_itinerary(market, lookup, stationary, &ship(Vec2::ZERO, &hold), 1)
+            .expect("Should be a profitable route");
+        let route = &itinerary.legs[0];
+
+        assert_eq!(route.store_to_buy_from, farm);
+        assert_eq!(route.store_to_sell_to, near_city);
+        assert_eq!(route.commodity, Commodity::Food);
+        assert_eq!(route.amount, 10);
+    }
+
+    #[test]
+    fn counts_the_trip_to_the_seller() {
+        let farm = Entity::from_raw(1);
+        let city = Entity::from_raw(2);
+        let planets = Planets::new([
+            (farm, Vec2::ZERO, store_with_food(400)),
+            (city, Vec2::new(100., 0.), store_with_food(10)),
+        ]);
+        let (market, lookup) = (&planets.market, planets.lookup());
+        let hold = Inventory::with_capacity(10);
+
+        let nearby = plan_itinerary(market, lookup, stationary, &ship(Vec2::ZERO, &hold), 1)
+            .expect("Should be a profitable route");
+        let far_away = plan_itinerary(
+            market,
+            lookup,
+            stationary,
+            &ship(Vec2::new(0., 3000.), &hold),
+            1,
+        )
+        .expect("Should be a profitable route");
+
+        assert!(far_away.travel_time() > nearby.travel_time());
+        assert!(far_away.profit_per_second() < nearby.profit_per_second());
+    }
+
+    #[test]
+    fn chains_trades_instead_of_flying_back_empty() {
+        let hydro = Entity::from_raw(1);
+        let forge = Entity::from_raw(2);
+        let terra = Entity::from_raw(3);
+        let mut hydro_store = store_with_food(0);
+        hydro_store
+            .give(Commodity::HydrogenTanks, 100)
+            .expect("Should fit");
+        let mut forge_store = store_with_food(0);
+        forge_store.give(Commodity::Fuel, 200).expect("Should fit");
+        let planets = Planets::new([
+            (hydro, Vec2::new(0., 0.), hydro_store),
+            (forge, Vec2::new(200., 0.), forge_store),
+            (terra, Vec2::new(400., 0.), store_with_food(0)),
+        ]);
+        let (market, lookup) = (&planets.market, planets.lookup());
+        let hold = Inventory::with_capacity(20);
+
+        let itinerary = plan_itinerary(market, lookup, stationary, &ship(Vec2::ZERO, &hold), 3)
+            .expect("Should be a profitable itinerary");
+
+        assert!(itinerary.legs.len() > 1, "{:#?}", itinerary);
+        for legs in itinerary.legs.windows(2) {
+            assert_eq!(legs[0].store_to_sell_to, legs[1].store_to_buy_from);
+        }
+        let single_leg = plan_itinerary(market, lookup, stationary, &ship(Vec2::ZERO, &hold), 1)
+            .expect("Should be a profitable route");
+        assert!(itinerary.profit_per_second() >= single_leg.profit_per_second());
+    }
+
+    #[test]
+    fn respects_constraints() {
+        let hydro = Entity::from_raw(1);
+        let forge = Entity::from_raw(2);
+        let terra = Entity::from_raw(3);
+        let mut hydro_store = store_with_food(100);
+        hydro_store
+            .give(Commodity::HydrogenTanks, 100)
+            .expect("Should fit");
+        let planets = Planets::new([
+            (hydro, Vec2::new(0., 0.), hydro_store),
+            (forge, Vec2::new(200., 0.), store_with_food(0)),
+            (terra, Vec2::new(-100., 0.), store_with_food(0)),
+        ]);
+        let (market, lookup) = (&planets.market, planets.lookup());
+        let hold = Inventory::with_capacity(20);
+        let shuttle = ShipProfile {
+            constraints: Constraints {
+                commodity: Some(Commodity::HydrogenTanks),
+                within: Some((Vec2::new(100., 0.), 100.)),
+            },
+            ..ship(Vec2::ZERO, &hold)
+        };
+
+        let itinerary = plan_itinerary(market, lookup, stationary, &shuttle, 3)
+            .expect("Should be a profitable itinerary");
+
+        for leg in &itinerary.legs {
+            assert_eq!(leg.commodity, Commodity::HydrogenTanks);
+            assert_ne!(leg.store_to_sell_to, terra);
+            assert_ne!(leg.store_to_buy_from, terra);
+        }
+    }
+
+    #[test]
+    fn plans_for_where_the_buyer_will_be() {
+        let farm = Entity::from_raw(1);
+        let city = Entity::from_raw(2);
+        let planets = Planets::new([
+            (farm, Vec2::ZERO, store_with_food(400)),
+            (city, Vec2::new(100., 0.), store_with_food(0)),
+        ]);
+        let (market, lookup) = (&planets.market, planets.lookup());
+        // the city is on its way out, a second from now it's a lot further off
+        let moving = |planet: Entity, seconds: f32| {
+            (planet == city).then_some(Vec2::new(100. + 400. * seconds.min(1.), 0.))
+        };
+        let hold = Inventory::with_capacity(10);
+        let ship = ship(Vec2::ZERO, &hold);
+
+        let listed = plan_itinerary(market, lookup, stationary, &ship, 1)
+            .expect("Should be a profitable route");
+        let predicted = plan_itinerary(market, lookup, moving, &ship, 1)
+            .expect("Should still be a profitable route");
+
+        assert_eq!(listed.legs[0].destination, Vec2::new(100., 0.));
+        assert_eq!(predicted.legs[0].destination, Vec2::new(500., 0.));
+        assert!(predicted.travel_time() > listed.travel_time());
+    }
+
+    #[test]
+    fn refuels_at_the_seller_when_the_tank_runs_low() {
+        let farm = Entity::from_raw(1);
+        let city = Entity::from_raw(2);
+        let mut farm_store = store_with_food(400);
+        farm_store.give(Commodity::Fuel, 50).expect("Should fit");
+        let planets = Planets::new([
+            (farm, Vec2::ZERO, farm_store),
+            (city, Vec2::new(1000., 0.), store_with_food(0)),
+        ]);
+        let (market, lookup) = (&planets.market, planets.lookup());
+        let hold = Inventory::with_capacity(10);
+        let full_tank = ship(Vec2::ZERO, &hold);
+        let low_on_fuel = ShipProfile {
+            fuel: 1.,
+            ..full_tank
+        };
+        let empty = ShipProfile {
+            fuel: 0.,
+            position: Vec2::new(0., 100.),
+            ..full_tank
+        };
+
+        let refuelled = plan_itinerary(market, lookup, stationary, &low_on_fuel, 1)
+            .expect("Should be a profitable route with a refuel stop");
+        let no_stop = plan_itinerary(market, lookup, stationary, &full_tank, 1)
+            .expect("Should be a profitable route");
+
+        assert_eq!(no_stop.legs[0].refuel, 0);
+        assert!(refuelled.legs[0].refuel > 0);
+        assert!(refuelled.legs[0].fuel_used <= 1. + refuelled.legs[0].refuel as f32);
+        assert!(plan_itinerary(market, lookup, stationary, &empty, 1).is_none());
+    }
+
+    #[test]
+    fn doesnt_count_on_more_fuel_than_the_seller_has() {
+        let farm = Entity::from_raw(1);
+        let city = Entity::from_raw(2);
+        let mut farm_store = store_with_food(400);
+        farm_store.give(Commodity::Fuel, 1).expect("Should fit");
+        let planets = Planets::new([
+            (farm, Vec2::ZERO, farm_store),
+            (city, Vec2::new(1000., 0.), store_with_food(0)),
+        ]);
+        let (market, lookup) = (&planets.market, planets.lookup());
+        let hold = Inventory::with_capacity(10);
+        // needs two units to make it to the city, the farm only has the one
+        let low_on_fuel = ShipProfile {
+            fuel: 0.1,
+            ..ship(Vec2::ZERO, &hold)
+        };
+
+        assert!(plan_itinerary(market, lookup, stationary, &low_on_fuel, 1).is_none());
+    }
+
+    #[test]
+    fn fuel_eats_into_profit() {
+        let farm = Entity::from_raw(1);
+        let city = Entity::from_raw(2);
+        let planets = Planets::new([
+            (farm, Vec2::ZERO, store_with_food(400)),
+            (city, Vec2::new(1000., 0.), store_with_food(0)),
+        ]);
+        let (market, lookup) = (&planets.market, planets.lookup());
+        let hold = Inventory::with_capacity(10);
+        let free_fuel = ship(Vec2::ZERO, &hold);
+        let pricey_fuel = ShipProfile {
+            fuel_price: 10.,
+            ..free_fuel
+        };
+        let outrageous_fuel = ShipProfile {
+            fuel_price: 1000.,
+            ..free_fuel
+        };
+
+        let free = plan_itinerary(market, lookup, stationary, &free_fuel, 1)
+            .expect("Should be profitable");
+        let pricey = plan_itinerary(market, lookup, stationary, &pricey_fuel, 1)
+            .expect("Should still be profitable");
+
+        assert!(pricey.profit() < free.profit());
+        assert!(plan_itinerary(market, lookup, stationary, &outrageous_fuel, 1).is_none());
+    }
+
+    #[test]
+    fn later_legs_see_what_earlier_ones_traded() {
+        let farm = Entity::from_raw(1);
+        let city = Entity::from_raw(2);
+        let planets = Planets::new([
+            (farm, Vec2::ZERO, store_with_food(400)),
+            (city, Vec2::new(100., 0.), store_with_food(0)),
+        ]);
+        let (market, lookup) = (&planets.market, planets.lookup());
+        let surroundings = Surroundings {
+            market,
+            store: &lookup,
+            position_in: &stationary,
+        };
+        let hold = Inventory::with_capacity(10);
+        let ship = ship(Vec2::ZERO, &hold);
+        let food_run = |so_far: &Itinerary| {
+            candidate_routes(&surroundings, &ship, Some(farm), so_far)
+                .into_iter()
+                .find(|route| route.commodity == Commodity::Food)
+        };
+
+        let first = food_run(&Itinerary::default()).expect("Farm has food for the city");
+        let second = food_run(&Itinerary {
+            legs: vec![first.clone()],
+        })
+        .expect("Still pays to bring the city food");
+
+        assert!(
+            second.price_to_sell_commodity < first.price_to_sell_commodity,
+            "The city pays less once it has food"
+        );
+        assert!(second.cost_to_buy_commodity >= first.cost_to_buy_commodity);
+    }
+}
@@ -0,0 +1,174 @@
+use bevy::prelude::*;
+
+use crate::v2::commodity::Commodity;
+use crate::v2::inventory::{Amount, Inventory};
+
+/// Units of food a thousand people eat every second
+pub const FOOD_PER_THOUSAND: f32 = 0.1;
+/// Seconds of missed meals people hold against their planet, going hungrier doesn't make it worse
+const HUNGER_MEMORY: f32 = 10.;
+/// Fraction of a starving population that dies out every second
+const STARVATION_RATE: f32 = 0.01;
+/// Seconds it takes unrest to catch up with how hungry people are
+const UNREST_RESPONSE: f32 = 20.;
+/// Fraction of a population in full unrest that leaves every second
+const EMIGRATION_RATE: f32 = 0.005;
+/// Thousands of people that stay put no matter what
+const MIN_SIZE: f32 = 0.1;
+
+/// The people living on a planet. They grow while fed, and shrink, riot and leave when they aren't
+#[derive(Debug, Clone, Component)]
+pub struct Population {
+    /// Thousands of people
+    size: f32,
+    /// Fraction the population grows by every second while well fed
+    pub growth_rate: f32,
+    /// Units of food the population went without
+    hunger: f32,
+    /// From 0, content, to 1, fed up and packing their bags
+    unrest: f32,
+}
+
+impl Population {
+    pub fn new(size: f32, growth_rate: f32) -> Self {
+        Self {
+            size,
+            growth_rate,
+            hunger: 0.,
+            unrest: 0.,
+        }
+    }
+
+    pub fn size(&self) -> f32 {
+        self.size
+    }
+
+    pub fn unrest(&self) -> f32 {
+        self.unrest
+    }
+
+    /// Whole units of food it takes to feed everyone for `seconds` and make up for missed meals
+    pub fn food_wanted(&self, seconds: f32) -> Amount {
+        (self.food_need(seconds) + self.hunger).round() as Amount
+    }
+
+    /// How short of food people are, from 0 when well fed to 1 when starving
+    pub fn shortage(&self) -> f32 {
+        let worst = self.food_need(HUNGER_MEMORY);
+        if worst <= 0. {
+            return 0.;
+        }
+        (self.hunger / worst).min(1.)
+    }
+
+    /// Eats `food` units over `seconds`, then grows or shrinks with how well fed people are.
+    /// Returns how many thousands of people left
+    pub fn live(&mut self, food: Amount, seconds: f32) -> f32 {
+        self.hunger = (self.hunger + self.food_need(seconds) - food as f32)
+            .clamp(0., self.food_need(HUNGER_MEMORY));
+        let shortage = self.shortage();
+        self.unrest += (shortage - self.unrest) * (seconds / UNREST_RESPONSE).min(1.);
+
+        let growth = self.growth_rate * (1. - shortage) - STARVATION_RATE * shortage;
+        self.size = (self.size * (1. + growth * seconds)).max(MIN_SIZE);
+        let emigrants = (self.size * EMIGRATION_RATE * self.unrest * seconds)
+            .min(self.size - MIN_SIZE)
+            .max(0.);
+        self.size -= emigrants;
+        emigrants
+    }
+
+    /// Eats what it wants from `pantry` over `seconds`, then lives with how well fed that left
+    /// people. Returns how many thousands of people left
+    pub fn eat_from(&mut self, pantry: &mut Inventory, seconds: f32) -> f32 {
+        let wanted = self.food_wanted(seconds);
+        let eaten = if wanted > 0 {
+            pantry.take(&Commodity::Food, wanted)
+        } else {
+            0
+        };
+        self.live(eaten, seconds)
+    }
+
+    /// Takes in people who left another planet
+    pub fn welcome(&mut self, thousands: f32) {
+        self.size += thousands;
+    }
+
+    fn food_need(&self, seconds: f32) -> f32 {
+        self.size * FOOD_PER_THOUSAND * seconds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bigger_populations_need_more_food() {
+        let small = Population::new(10., 0.01);
+        let big = Population::new(100., 0.01);
+
+        assert_eq!(small.food_wanted(1.), 1);
+        assert_eq!(big.food_wanted(1.), 10);
+    }
+
+    #[test]
+    fn grows_while_fed() {
+        let mut population = Population::new(10., 0.01);
+
+        for _ in 0..10 {
+            // a little extra so rounding never leaves anyone hungry
+            let food = population.food_wanted(1.) + 1;
+            assert_eq!(population.live(food, 1.), 0.);
+        }
+
+        assert!(population.size() > 10.);
+        assert_eq!(population.unrest(), 0.);
+    }
+
+    #[test]
+    fn starving_people_die_out_riot_and_leave() {
+        let mut population = Population::new(10., 0.01);
+
+        let mut emigrants = 0.;
+        for _ in 0..30 {
+            emigrants += population.live(0, 1.);
+        }
+
+        assert_eq!(population.shortage(), 1.);
+        assert!(population.unrest() > 0.5);
+        assert!(emigrants > 0.);
+        assert!(population.size() + emigrants < 10.);
+    }
+
+    #[test]
+    fn small_populations_eat_once_theyve_worked_up_an_appetite() {
+        let mut population = Population::new(1., 0.01);
+        let mut pantry = Inventory::with_food_and_capacity(10, 100);
+
+        // a tenth of a unit a second doesn't round up to a meal yet
+        population.eat_from(&mut pantry, 1.);
+        assert_eq!(pantry.get(&Commodity::Food), 10);
+
+        for _ in 0..9 {
+            population.eat_from(&mut pantry, 1.);
+        }
+        assert!(pantry.get(&Commodity::Food) < 10);
+    }
+
+    #[test]
+    fn missed_meals_are_made_up_for_before_people_calm_down() {
+        let mut population = Population::new(10., 0.01);
+        for _ in 0..5 {
+            population.live(0, 1.);
+        }
+        assert!(population.food_wanted(1.) > 5);
+
+        let food = population.food_wanted(1.) + 1;
+        population.live(food, 1.);
+
+        assert_eq!(population.shortage(), 0.);
+        assert_eq!(population.food_wanted(1.), 1);
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use crate::v2::commodity::Commodity;
+use crate::v2::inventory::Amount;
+use crate::v2::store::Credits;
+
+/// What a store knows about a commodity when it sets a price
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MarketConditions {
+    pub stock: Amount,
+    /// Units per second that have been leaving the store lately
+    pub consumption_rate: f32,
+}
+
+pub trait PricingModel: Send + Sync {
+    /// What one unit is worth, the store quotes its bid and ask around this. `None` if the
+    /// store doesn't trade `commodity` at all
+    fn mid_price(&self, commodity: Commodity, conditions: MarketConditions) -> Option<f32>;
+
+    /// The most the store may charge for a unit of `commodity`, `None` if there's no ceiling
+    fn max_price(&self, _commodity: Commodity) -> Option<Credits> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CommodityPricing {
+    /// How much the store wants to keep in stock
+    pub target_stock: Amount,
+    /// The floor, selling for less than this is a loss for whoever produced it
+    pub production_cost: Credits,
+    /// The ceiling, the most any buyer is willing and able to pay
+    pub max_price: Credits,
+}
+
+/// Prices go up as stock falls below the target and down as it piles up above it.
+/// Stores that sell a lot want more in stock, so consumption pushes prices up too
+pub struct SupplyAndDemand {
+    pub commodities: HashMap<Commodity, CommodityPricing>,
+    /// How many seconds worth of consumption to keep in stock on top of the target
+    pub seconds_of_cover: f32,
+}
+
+impl Default for SupplyAndDemand {
+    fn default() -> Self {
+        let commodities = HashMap::from([
+            (
+                Commodity::Food,
+                CommodityPricing {
+                    target_stock: 100,
+                    production_cost: 1,
+                    max_price: 9,
+                },
+            ),
+            (
+                Commodity::HydrogenTanks,
+                CommodityPricing {
+                    target_stock: 100,
+                    production_cost: 1,
+                    max_price: 8,
+                },
+            ),
+            (
+                Commodity::Fuel,
+                CommodityPricing {
+                    target_stock: 50,
+                    production_cost: 3,
+                    max_price: 20,
+                },
+            ),
+        ]);
+        Self {
+            commodities,
+            seconds_of_cover: 30.,
+        }
+    }
+}
+
+impl PricingModel for SupplyAndDemand {
+    /// Ceiling when out of stock, halfway at the target, approaching the floor as stock piles up
+    fn mid_price(&self, commodity: Commodity, conditions: MarketConditions) -> Option<f32> {
+        let pricing = self.commodities.get(&commodity)?;
+        let target =
+            pricing.target_stock as f32 + conditions.consumption_rate * self.seconds_of_cover;
+        let scarcity = target / (target + conditions.stock as f32);
+        let floor = pricing.production_cost as f32;
+        let ceiling = pricing.max_price as f32;
+        Some(floor + (ceiling - floor) * scarcity)
+    }
+
+    fn max_price(&self, commodity: Commodity) -> Option<Credits> {
+        self.commodities
+            .get(&commodity)
+            .map(|pricing| pricing.max_price)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conditions(stock: Amount) -> MarketConditions {
+        MarketConditions {
+            stock,
+            consumption_rate: 0.,
+        }
+    }
+
+    #[test]
+    fn prices_stay_between_floor_and_ceiling() {
+        let pricing = SupplyAndDemand::default();
+
+        for stock in [0, 1, 10, 100, 1_000, 100_000] {
+            let price = pricing
+                .mid_price(Commodity::Fuel, conditions(stock))
+                .expect("Fuel is priced");
+            assert!((3. ..=20.).contains(&price), "{}", price);
+        }
+    }
+
+    #[test]
+    fn scarce_commodities_are_pricier() {
+        let pricing = SupplyAndDemand::default();
+
+        let price = |stock| {
+            pricing
+                .mid_price(Commodity::Food, conditions(stock))
+                .expect("Food is priced")
+        };
+        let scarce = price(10);
+        let plentiful = price(400);
+
+        assert!(scarce > plentiful, "{} vs {}", scarce, plentiful);
+    }
+
+    #[test]
+    fn consumption_drives_prices_up() {
+        let pricing = SupplyAndDemand::default();
+
+        let idle = pricing
+            .mid_price(Commodity::Food, conditions(200))
+            .expect("Food is priced");
+        let busy = pricing
+            .mid_price(
+                Commodity::Food,
+                MarketConditions {
+                    stock: 200,
+                    consumption_rate: 5.,
+                },
+            )
+            .expect("Food is priced");
+
+        assert!(busy > idle, "{} vs {}", busy, idle);
+    }
+
+    #[test]
+    fn commodities_without_pricing_have_no_price() {
+        let pricing = SupplyAndDemand {
+            commodities: HashMap::new(),
+            ..SupplyAndDemand::default()
+        };
+
+        assert_eq!(pricing.mid_price(Commodity::Food, conditions(10)), None);
+    }
+}
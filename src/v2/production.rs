@@ -0,0 +1,326 @@
+use std::ops::Not;
+
+use bevy::prelude::*;
+
+use crate::v2::commodity::Commodity;
+use crate::v2::deposit::Deposits;
+use crate::v2::inventory::{Amount, Inventory};
+use crate::v2::store::Credits;
+
+/// What a facility turns into what, every cycle
+#[derive(Debug, Clone)]
+pub struct Recipe {
+    pub inputs: Vec<(Commodity, Amount)>,
+    pub outputs: Vec<(Commodity, Amount)>,
+    /// Seconds per cycle
+    pub cycle_time: f32,
+    /// What the workers get paid for every batch
+    pub wages: Credits,
+    /// The outputs come out of the planet's deposits, as much as they still give up
+    pub from_deposits: bool,
+}
+
+impl Recipe {
+    fn volume(ingredients: &[(Commodity, Amount)]) -> Amount {
+        ingredients
+            .iter()
+            .map(|(commodity, amount)| commodity.volume() * amount)
+            .sum()
+    }
+}
+
+/// A farm, mine or factory at a planet. It buys its inputs from the planet's store and sells its
+/// outputs back to it
+#[derive(Debug, Clone, Component)]
+pub struct Facility {
+    /// Where it trades
+    pub planet: Entity,
+    pub recipe: Recipe,
+    /// How many batches the facility works on at once
+    pub throughput: Amount,
+    /// Seconds into the current cycle
+    progress: f32,
+    /// Shut down facilities make nothing
+    pub running: bool,
+}
+
+impl Facility {
+    pub fn new(planet: Entity, recipe: Recipe, throughput: Amount) -> Self {
+        Self {
+            planet,
+            recipe,
+            throughput,
+            progress: 0.,
+            running: true,
+        }
+    }
+
+    /// Works for `seconds`, taking inputs from and putting outputs in `inventory` at the end of
+    /// each cycle. A finished cycle waits for inputs and room before starting the next.
+    /// Returns how many batches were made
+    pub fn run(
+        &mut self,
+        seconds: f32,
+        inventory: &mut Inventory,
+        deposits: &mut Deposits,
+    ) -> Amount {
+        if self.running.not() {
+            return 0;
+        }
+        self.progress = (self.progress + seconds).min(self.recipe.cycle_time);
+        if self.progress < self.recipe.cycle_time {
+            return 0;
+        }
+        let batches = self.batches_possible(inventory);
+        // nothing left in the ground is as good as no inputs
+        let exhausted =
+            self.recipe.from_deposits
+                && self.recipe.outputs.iter().all(|(commodity, amount)| {
+                    deposits.extractable(*commodity, amount * batches) == 0
+                });
+        if batches == 0 || exhausted {
+            return 0;
+        }
+        self.progress = 0.;
+        for (commodity, amount) in &self.recipe.inputs {
+            inventory.take(commodity, amount * batches);
+        }
+        for (commodity, amount) in &self.recipe.outputs {
+            let amount = if self.recipe.from_deposits {
+                deposits.extract(*commodity, amount * batches)
+            } else {
+                amount * batches
+            };
+            if amount > 0 {
+                inventory
+                    .add(*commodity, amount)
+                    .expect("Checked there's room for the outputs");
+            }
+        }
+        batches
+    }
+
+    /// Credits a batch makes after buying the inputs and paying the wages, `None` if the inputs
+    /// aren't for sale
+    pub fn margin(
+        &self,
+        buy_price: impl Fn(Commodity) -> Option<Credits>,
+        sell_price: impl Fn(Commodity) -> Option<Credits>,
+        deposits: &Deposits,
+    ) -> Option<i64> {
+        let costs: Credits = self
+            .recipe
+            .inputs
+            .iter()
+            .map(|(commodity, amount)| Some(buy_price(*commodity)? * amount))
+            .sum::<Option<Credits>>()?;
+        let revenue: Credits = self
+            .recipe
+            .outputs
+            .iter()
+            .map(|(commodity, amount)| {
+                let amount = if self.recipe.from_deposits {
+                    deposits.extractable(*commodity, *amount)
+                } else {
+                    *amount
+                };
+                sell_price(*commodity).unwrap_or(0) * amount
+            })
+            .sum();
+        Some(revenue as i64 - costs as i64 - self.recipe.wages as i64)
+    }
+
+    /// Batches there are inputs and room for, up to the throughput
+    fn batches_possible(&self, inventory: &Inventory) -> Amount {
+        let inputs = Recipe::volume(&self.recipe.inputs);
+        let outputs = Recipe::volume(&self.recipe.outputs);
+        (0..=self.throughput)
+            .rev()
+            .find(|batches| {
+                let have_inputs = self
+                    .recipe
+                    .inputs
+                    .iter()
+                    .all(|(commodity, amount)| inventory.get(commodity) >= amount * batches);
+                // the inputs make room as they're used up
+                let have_room = outputs * batches <= inventory.space_left() + inputs * batches;
+                have_inputs && have_room
+            })
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::v2::deposit::Deposit;
+
+    fn refinery(throughput: Amount) -> Facility {
+        Facility::new(
+            Entity::from_raw(1),
+            Recipe {
+                inputs: vec![(Commodity::HydrogenTanks, 2)],
+                outputs: vec![(Commodity::Fuel, 1)],
+                cycle_time: 2.,
+                wages: 1,
+                from_deposits: false,
+            },
+            throughput,
+        )
+    }
+
+    fn inventory_with_hydrogen(amount: Amount) -> Inventory {
+        let mut inventory = Inventory::with_capacity(100);
+        inventory
+            .add(Commodity::HydrogenTanks, amount)
+            .expect("Should fit");
+        inventory
+    }
+
+    #[test]
+    fn turns_inputs_into_outputs_every_cycle() {
+        let mut facility = refinery(1);
+        let mut inventory = inventory_with_hydrogen(10);
+        let mut deposits = Deposits::default();
+
+        assert_eq!(facility.run(1., &mut inventory, &mut deposits), 0);
+        assert_eq!(facility.run(1., &mut inventory, &mut deposits), 1);
+
+        assert_eq!(inventory.get(&Commodity::HydrogenTanks), 8);
+        assert_eq!(inventory.get(&Commodity::Fuel), 1);
+    }
+
+    #[test]
+    fn throughput_is_how_many_batches_at_once() {
+        let mut facility = refinery(3);
+        let mut inventory = inventory_with_hydrogen(10);
+        let mut deposits = Deposits::default();
+
+        assert_eq!(facility.run(2., &mut inventory, &mut deposits), 3);
+
+        assert_eq!(inventory.get(&Commodity::HydrogenTanks), 4);
+        assert_eq!(inventory.get(&Commodity::Fuel), 3);
+    }
+
+    #[test]
+    fn waits_for_inputs() {
+        let mut facility = refinery(3);
+        let mut inventory = inventory_with_hydrogen(3);
+        let mut deposits = Deposits::default();
+
+        assert_eq!(facility.run(2., &mut inventory, &mut deposits), 1);
+        assert_eq!(facility.run(2., &mut inventory, &mut deposits), 0);
+
+        // a finished cycle is ready to go as soon as there's hydrogen
+        inventory
+            .add(Commodity::HydrogenTanks, 1)
+            .expect("Should fit");
+        assert_eq!(facility.run(0., &mut inventory, &mut deposits), 1);
+    }
+
+    #[test]
+    fn stops_when_theres_no_room_for_the_outputs() {
+        let mut farm = Facility::new(
+            Entity::from_raw(1),
+            Recipe {
+                inputs: vec![],
+                outputs: vec![(Commodity::Food, 10)],
+                cycle_time: 1.,
+                wages: 1,
+                from_deposits: false,
+            },
+            1,
+        );
+        let mut inventory = Inventory::with_capacity(15);
+        let mut deposits = Deposits::default();
+
+        assert_eq!(farm.run(1., &mut inventory, &mut deposits), 1);
+        assert_eq!(farm.run(1., &mut inventory, &mut deposits), 0);
+        assert_eq!(inventory.get(&Commodity::Food), 10);
+    }
+
+    #[test]
+    fn extractors_get_less_out_of_a_depleted_deposit() {
+        let mut vents = Facility::new(
+            Entity::from_raw(1),
+            Recipe {
+                inputs: vec![],
+                outputs: vec![(Commodity::HydrogenTanks, 10)],
+                cycle_time: 1.,
+                wages: 1,
+                from_deposits: true,
+            },
+            1,
+        );
+        let mut inventory = Inventory::with_capacity(1000);
+        let mut deposits = Deposits::new(vec![Deposit::new(Commodity::HydrogenTanks, 20., 0.)]);
+        let price = |_| Some(1);
+
+        assert_eq!(vents.margin(price, price, &deposits), Some(9));
+        vents.run(1., &mut inventory, &mut deposits);
+        assert_eq!(vents.margin(price, price, &deposits), Some(4));
+        vents.run(1., &mut inventory, &mut deposits);
+
+        assert_eq!(inventory.get(&Commodity::HydrogenTanks), 15);
+    }
+
+    #[test]
+    fn extractors_make_nothing_once_the_deposit_is_gone() {
+        let mut vents = Facility::new(
+            Entity::from_raw(1),
+            Recipe {
+                inputs: vec![],
+                outputs: vec![(Commodity::HydrogenTanks, 10)],
+                cycle_time: 1.,
+                wages: 1,
+                from_deposits: true,
+            },
+            1,
+        );
+        let mut inventory = Inventory::with_capacity(1000);
+        let mut deposits = Deposits::new(vec![Deposit::new(Commodity::HydrogenTanks, 20., 0.)]);
+
+        let mut batches = 0;
+        for _ in 0..100 {
+            batches += vents.run(1., &mut inventory, &mut deposits);
+        }
+
+        assert_eq!(inventory.get(&Commodity::HydrogenTanks), 20);
+        assert_eq!(vents.run(1., &mut inventory, &mut deposits), 0);
+        assert!(batches < 100);
+    }
+
+    #[test]
+    fn shut_down_facilities_make_nothing() {
+        let mut facility = refinery(1);
+        facility.running = false;
+        let mut inventory = inventory_with_hydrogen(10);
+        let mut deposits = Deposits::default();
+
+        assert_eq!(facility.run(2., &mut inventory, &mut deposits), 0);
+        assert_eq!(inventory.get(&Commodity::HydrogenTanks), 10);
+    }
+
+    #[test]
+    fn margin_is_what_the_outputs_fetch_less_inputs_and_wages() {
+        let facility = refinery(1);
+        let deposits = Deposits::default();
+        let prices = |hydrogen: Credits, fuel: Credits| {
+            move |commodity| match commodity {
+                Commodity::HydrogenTanks => Some(hydrogen),
+                Commodity::Fuel => Some(fuel),
+                Commodity::Food => None,
+            }
+        };
+
+        assert_eq!(
+            facility.margin(prices(2, 0), prices(0, 10), &deposits),
+            Some(5)
+        );
+        assert_eq!(
+            facility.margin(prices(6, 0), prices(0, 10), &deposits),
+            Some(-3)
+        );
+        assert_eq!(facility.margin(|_| None, prices(0, 10), &deposits), None);
+    }
+}
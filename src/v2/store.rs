@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::ops::Not;
 
 use bevy::prelude::*;
 use strum::IntoEnumIterator;
@@ -6,15 +7,21 @@ use uuid::Uuid;
 
 use crate::v2::commodity::Commodity;
 use crate::v2::inventory::Amount;
-use crate::v2::inventory::Inventory;
+use crate::v2::inventory::{Inventory, InventoryError, OverflowPolicy};
+use crate::v2::ledger::{Ledger, Wallet};
+use crate::v2::order_book::Side;
+use crate::v2::pricing::{MarketConditions, PricingModel, SupplyAndDemand};
 
 pub type Credits = u64;
+pub type ReservationId = Uuid;
 
 #[derive(Debug)]
 pub struct Receipt {
     pub commodity: Commodity,
     pub amount: Amount,
-    pub price: Credits,
+    /// Average price paid per unit, prices change as stock moves
+    pub unit_price: Credits,
+    pub total: Credits,
 }
 
 #[derive(Debug)]
@@ -24,88 +31,387 @@ pub struct StoreListing {
     pub price: Credits,
 }
 
+/// What trading a quantity would cost or earn, walking the price as stock changes
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Quote {
+    pub commodity: Commodity,
+    /// How much of the requested amount can be filled
+    pub amount: Amount,
+    pub total: Credits,
+    /// Price of the last unit filled
+    pub marginal_price: Credits,
+}
+
+impl Quote {
+    const fn empty(commodity: Commodity) -> Self {
+        Self {
+            commodity,
+            amount: 0,
+            total: 0,
+            marginal_price: 0,
+        }
+    }
+
+    fn add(&mut self, price: Credits) {
+        self.amount += 1;
+        self.total += price;
+        self.marginal_price = price;
+    }
+
+    pub const fn average_price(&self) -> Credits {
+        self.total / self.amount
+    }
+}
+
+impl From<Quote> for Receipt {
+    fn from(quote: Quote) -> Self {
+        Receipt {
+            commodity: quote.commodity,
+            amount: quote.amount,
+            unit_price: quote.average_price(),
+            total: quote.total,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum OrderMode {
+    /// Either the whole amount is traded or nothing is
+    AllOrNothing,
+    /// Trade as much as stock, prices and funds allow
+    Partial,
+}
+
+/// How much a store's warehouse can hold, across all commodities
+const WAREHOUSE_CAPACITY: Amount = 500;
+
+const DEFAULT_SPREAD: f32 = 0.2;
+
+/// How much weight the latest period gets when updating the consumption rate
+const CONSUMPTION_SMOOTHING: f32 = 0.2;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Consumption {
+    this_period: Amount,
+    rate: f32,
+}
+
+/// Stock or warehouse space held for a trader that's on its way
+#[derive(Debug, Clone, Copy)]
+struct Reservation {
+    id: ReservationId,
+    /// `Buy` holds stock for a buyer, `Sell` holds warehouse space for a seller
+    side: Side,
+    commodity: Commodity,
+    amount: Amount,
+    /// Elapsed game time in seconds after which the reservation is dropped
+    expires_at: f32,
+}
+
+/// Trades counted on at a store but not made yet, quotes for later trades price them in
+#[derive(Debug, Default, Clone)]
+pub struct Planned {
+    /// Units added to the stock, negative for units taken out
+    stock: HashMap<Commodity, i64>,
+    /// What the store pays for what's sold to it
+    paid: Credits,
+}
+
+impl Planned {
+    pub fn buy(&mut self, commodity: Commodity, amount: Amount) {
+        *self.stock.entry(commodity).or_default() -= amount as i64;
+    }
+
+    pub fn sell(&mut self, commodity: Commodity, amount: Amount, total: Credits) {
+        *self.stock.entry(commodity).or_default() += amount as i64;
+        self.paid += total;
+    }
+
+    fn stock_change(&self, commodity: Commodity) -> i64 {
+        self.stock.get(&commodity).copied().unwrap_or_default()
+    }
+}
+
+/// Moves `amount` by `change` without going below zero
+fn adjusted(amount: Amount, change: i64) -> Amount {
+    (amount as i64 + change).max(0) as Amount
+}
+
 #[derive(Component)]
 pub struct Store {
     pub id: Uuid,
     pub inventory: Inventory,
+    pub wallet: Wallet,
+    pub pricing: Box<dyn PricingModel>,
+    /// Gap between what the store charges and what it pays, as a fraction of the mid price
+    spread: f32,
+    consumption: HashMap<Commodity, Consumption>,
+    reservations: Vec<Reservation>,
 }
 
 impl Default for Store {
     fn default() -> Self {
         let mut store = Self {
             id: Uuid::new_v4(),
-            inventory: Inventory::default(),
+            // whatever doesn't fit in the warehouse goes to waste
+            inventory: Inventory::with_capacity_and_overflow(
+                WAREHOUSE_CAPACITY,
+                OverflowPolicy::Spill,
+            ),
+            wallet: Wallet::default(),
+            pricing: Box::<SupplyAndDemand>::default(),
+            spread: DEFAULT_SPREAD,
+            consumption: HashMap::new(),
+            reservations: vec![],
         };
-        store.give(Commodity::Food, 100);
+        store
+            .give(Commodity::Food, 100)
+            .expect("New store should have room for food");
         store
     }
 }
 
 impl Store {
-    pub fn give(&mut self, commodity: Commodity, amount: Amount) {
-        self.inventory.add(commodity, amount);
+    /// Quotes `spread`, as a fraction of the mid price, between what the store charges and what
+    /// it pays. Without one a round trip through the store could make money
+    pub fn with_spread(self, spread: f32) -> Self {
+        assert!(
+            spread > 0.,
+            "A store's spread has to be positive, got {}",
+            spread
+        );
+        Self { spread, ..self }
+    }
+
+    pub fn give(&mut self, commodity: Commodity, amount: Amount) -> Result<Amount, InventoryError> {
+        self.inventory.add(commodity, amount)
     }
 
     pub fn take(&mut self, commodity: Commodity, amount: Amount) {
         let _ = self.inventory.take(&commodity, amount);
     }
 
+    /// Buy up to `amount` units, `max_price` is the most the buyer is willing to pay for any one unit
     pub fn buy_from_store(
         &mut self,
         commodity: Commodity,
         amount: Amount,
-        price: Option<Credits>,
+        max_price: Option<Credits>,
+        mode: OrderMode,
+        buyer: &mut Wallet,
+        ledger: &mut Ledger,
     ) -> Option<Receipt> {
-        if self.inventory.get(&commodity) < amount {
-            info!("Not enough {:?}", commodity);
+        let quote = self
+            .walk_buy(
+                commodity,
+                amount,
+                max_price,
+                Some(buyer.credits()),
+                &Planned::default(),
+            )
+            .filter(|quote| mode == OrderMode::Partial || quote.amount == amount);
+        let Some(quote) = quote else {
+            info!("Store won't sell {} {:?}", amount, commodity);
+            return None;
+        };
+        if let Err(e) = ledger.transfer(buyer, &mut self.wallet, quote.total) {
+            info!(
+                "Buyer can't afford {} {:?}: {:?}",
+                quote.amount, commodity, e
+            );
             return None;
         }
-        match self.price_check_buy_specific_from_store(commodity) {
-            None => {
-                info!("Store doesn't sell {:?}", commodity);
-                None
-            }
-
-            Some(store_price) => {
-                if price.is_none() || price.unwrap() == store_price.price {
-                    self.take(commodity, amount);
-                    Some(Receipt {
-                        commodity,
-                        amount,
-                        price: store_price.price,
-                    })
-                } else {
-                    info!("Store doesn't sell {:?} for that price", commodity);
-                    None
-                }
-            }
-        }
+        self.take(commodity, quote.amount);
+        self.consumption.entry(commodity).or_default().this_period += quote.amount;
+        Some(quote.into())
     }
 
+    /// Sell up to `amount` units, `min_price` is the least the seller will accept for any one unit
     pub fn sell_to_store(
         &mut self,
         commodity: Commodity,
         amount: Amount,
-        price: Option<Credits>,
+        min_price: Option<Credits>,
+        mode: OrderMode,
+        seller: &mut Wallet,
+        ledger: &mut Ledger,
     ) -> Option<Receipt> {
-        match self.price_check_sell_specific_to_store(commodity) {
-            None => {
-                info!("Store doesn't want to buy {:?}", commodity);
-                None
+        let quote = self
+            .walk_sell(commodity, amount, min_price, &Planned::default())
+            .filter(|quote| mode == OrderMode::Partial || quote.amount == amount);
+        let Some(quote) = quote else {
+            info!("Store won't buy {} {:?}", amount, commodity);
+            return None;
+        };
+        if let Err(e) = ledger.transfer(&mut self.wallet, seller, quote.total) {
+            info!(
+                "Store can't afford {} {:?}: {:?}",
+                quote.amount, commodity, e
+            );
+            return None;
+        }
+        self.give(commodity, quote.amount)
+            .expect("Store only buys what fits in the warehouse");
+        Some(quote.into())
+    }
+
+    /// What buying `amount` units would cost, prices rise as the stock runs down
+    pub fn quote_buy(&self, commodity: Commodity, amount: Amount) -> Option<Quote> {
+        self.walk_buy(commodity, amount, None, None, &Planned::default())
+    }
+
+    /// Like `quote_buy` but stops when `budget` runs out
+    pub fn quote_buy_with_budget(
+        &self,
+        commodity: Commodity,
+        amount: Amount,
+        budget: Credits,
+    ) -> Option<Quote> {
+        self.walk_buy(commodity, amount, None, Some(budget), &Planned::default())
+    }
+
+    /// Like `quote_buy_with_budget` once the `planned` trades have gone through
+    pub fn quote_buy_after(
+        &self,
+        planned: &Planned,
+        commodity: Commodity,
+        amount: Amount,
+        budget: Option<Credits>,
+    ) -> Option<Quote> {
+        self.walk_buy(commodity, amount, None, budget, planned)
+    }
+
+    /// What selling `amount` units would earn, prices fall as the stock piles up.
+    /// Limited by warehouse space and the store's money
+    pub fn quote_sell(&self, commodity: Commodity, amount: Amount) -> Option<Quote> {
+        self.walk_sell(commodity, amount, None, &Planned::default())
+    }
+
+    /// Like `quote_sell` once the `planned` trades have gone through
+    pub fn quote_sell_after(
+        &self,
+        planned: &Planned,
+        commodity: Commodity,
+        amount: Amount,
+    ) -> Option<Quote> {
+        self.walk_sell(commodity, amount, None, planned)
+    }
+
+    /// Prices units one at a time as the stock goes down, until one of the limits is hit
+    fn walk_buy(
+        &self,
+        commodity: Commodity,
+        amount: Amount,
+        max_price: Option<Credits>,
+        budget: Option<Credits>,
+        planned: &Planned,
+    ) -> Option<Quote> {
+        let mut stock = adjusted(
+            self.unreserved_stock(commodity),
+            planned.stock_change(commodity),
+        );
+        let mut quote = Quote::empty(commodity);
+        while quote.amount < amount {
+            let Some(price) = self.buy_price(commodity, stock) else {
+                break;
+            };
+            if max_price.is_some_and(|max_price| price > max_price)
+                || budget.is_some_and(|budget| quote.total + price > budget)
+            {
+                break;
             }
+            quote.add(price);
+            stock -= 1;
+        }
+        (quote.amount > 0).then_some(quote)
+    }
 
-            Some(store_price) => {
-                if price.is_none() || price.unwrap() == store_price.price {
-                    self.give(commodity, amount);
-                    Some(Receipt {
-                        commodity,
-                        amount,
-                        price: store_price.price,
-                    })
-                } else {
-                    None
-                }
+    /// Prices units one at a time as the stock goes up, until one of the limits is hit
+    fn walk_sell(
+        &self,
+        commodity: Commodity,
+        amount: Amount,
+        min_price: Option<Credits>,
+        planned: &Planned,
+    ) -> Option<Quote> {
+        let change = planned.stock_change(commodity);
+        let mut stock = adjusted(
+            self.inventory.get(&commodity) + self.reserved(Side::Sell, commodity),
+            change,
+        );
+        let room_for = adjusted(self.unreserved_room_for(commodity), -change);
+        let mut quote = Quote::empty(commodity);
+        while quote.amount < amount.min(room_for) {
+            let Some(price) = self.sell_price(commodity, stock) else {
+                break;
+            };
+            if min_price.is_some_and(|min_price| price < min_price)
+                || self
+                    .wallet
+                    .can_afford(planned.paid + quote.total + price)
+                    .not()
+            {
+                break;
             }
+            quote.add(price);
+            stock += 1;
+        }
+        (quote.amount > 0).then_some(quote)
+    }
+
+    /// What the store charges for one unit when it has `stock` of it
+    fn buy_price(&self, commodity: Commodity, stock: Amount) -> Option<Credits> {
+        if stock == 0 {
+            return None;
+        }
+        let mid_price = self
+            .pricing
+            .mid_price(commodity, self.market_conditions(commodity, stock))?;
+        Some(self.ask(commodity, mid_price))
+    }
+
+    /// Half the spread above the mid price, but never above what any buyer would pay
+    fn ask(&self, commodity: Commodity, mid_price: f32) -> Credits {
+        let ask = (mid_price * (1. + self.spread / 2.)).ceil() as Credits;
+        self.pricing
+            .max_price(commodity)
+            .map_or(ask, |max_price| ask.min(max_price))
+    }
+
+    /// What the store pays for one unit when it has `stock` of it.
+    /// Priced as if the unit was already in stock, so it's always below what the store
+    /// would charge to sell that same unit back
+    fn sell_price(&self, commodity: Commodity, stock: Amount) -> Option<Credits> {
+        let mid_price = self
+            .pricing
+            .mid_price(commodity, self.market_conditions(commodity, stock + 1))?;
+        let ask = self.ask(commodity, mid_price);
+        let price =
+            ((mid_price * (1. - self.spread / 2.)).floor() as Credits).min(ask.saturating_sub(1));
+        (price > 0).then_some(price)
+    }
+
+    fn market_conditions(&self, commodity: Commodity, stock: Amount) -> MarketConditions {
+        MarketConditions {
+            stock,
+            consumption_rate: self.consumption_rate(commodity),
+        }
+    }
+
+    /// Units per second leaving the store, smoothed over the last few periods
+    pub fn consumption_rate(&self, commodity: Commodity) -> f32 {
+        self.consumption
+            .get(&commodity)
+            .map(|consumption| consumption.rate)
+            .unwrap_or(0.)
+    }
+
+    /// Should be called once per second to fold what was sold into the consumption rates
+    pub fn end_consumption_period(&mut self) {
+        for consumption in self.consumption.values_mut() {
+            consumption.rate = consumption.rate * (1. - CONSUMPTION_SMOOTHING)
+                + consumption.this_period as f32 * CONSUMPTION_SMOOTHING;
+            consumption.this_period = 0;
         }
     }
 
@@ -113,60 +419,85 @@ impl Store {
         &self,
         commodity: Commodity,
     ) -> Option<StoreListing> {
-        let amount_stockpiled = self.inventory.get(&commodity);
-        let max_sellable = amount_stockpiled.max(20);
-        if amount_stockpiled > 200 {
-            Some(StoreListing {
-                commodity,
-                amount: max_sellable,
-                price: 1,
-            })
-        } else if amount_stockpiled > 100 {
-            Some(StoreListing {
+        let amount_stockpiled = self.unreserved_stock(commodity);
+        self.buy_price(commodity, amount_stockpiled)
+            .map(|price| StoreListing {
                 commodity,
-                amount: max_sellable,
-                price: 2,
+                amount: amount_stockpiled,
+                price,
             })
-        } else if amount_stockpiled < 30 {
-            None
-        } else {
-            Some(StoreListing {
-                commodity,
-                amount: max_sellable,
-                price: 5,
-            })
-        }
     }
 
     pub fn price_check_sell_specific_to_store(&self, commodity: Commodity) -> Option<StoreListing> {
-        let amount_stockpiled = self.inventory.get(&commodity);
-        if amount_stockpiled < 20 {
-            Some(StoreListing {
-                commodity,
-                amount: 400, // todo make smarter
-                price: 10,
-            })
-        } else if amount_stockpiled > 200 {
-            None
-        } else if amount_stockpiled > 100 {
-            Some(StoreListing {
-                commodity,
-                amount: 400, // todo make smarter
-                price: 1,
-            })
-        } else {
-            Some(StoreListing {
-                commodity,
-                amount: 400, // todo make smarter
-                price: 3,
-            })
-        }
+        let amount_stockpiled =
+            self.inventory.get(&commodity) + self.reserved(Side::Sell, commodity);
+        let price = self.sell_price(commodity, amount_stockpiled)?;
+        let amount = self
+            .quote_sell(commodity, self.unreserved_room_for(commodity))
+            .map(|quote| quote.amount)
+            .unwrap_or(0);
+        Some(StoreListing {
+            commodity,
+            amount,
+            price,
+        })
+    }
+
+    /// Holds stock (`Buy`) or warehouse space (`Sell`) so nobody else plans around it
+    pub fn reserve(
+        &mut self,
+        side: Side,
+        commodity: Commodity,
+        amount: Amount,
+        expires_at: f32,
+    ) -> ReservationId {
+        let id = Uuid::new_v4();
+        self.reservations.push(Reservation {
+            id,
+            side,
+            commodity,
+            amount,
+            expires_at,
+        });
+        id
+    }
+
+    pub fn release(&mut self, id: ReservationId) {
+        self.reservations.retain(|reservation| reservation.id != id);
+    }
+
+    pub fn remove_expired_reservations(&mut self, now: f32) {
+        self.reservations
+            .retain(|reservation| reservation.expires_at > now);
+    }
+
+    fn reserved(&self, side: Side, commodity: Commodity) -> Amount {
+        self.reservations
+            .iter()
+            .filter(|reservation| reservation.side == side && reservation.commodity == commodity)
+            .map(|reservation| reservation.amount)
+            .sum()
+    }
+
+    fn unreserved_stock(&self, commodity: Commodity) -> Amount {
+        self.inventory
+            .get(&commodity)
+            .saturating_sub(self.reserved(Side::Buy, commodity))
+    }
+
+    fn unreserved_room_for(&self, commodity: Commodity) -> Amount {
+        let reserved_space: Amount = self
+            .reservations
+            .iter()
+            .filter(|reservation| reservation.side == Side::Sell)
+            .map(|reservation| reservation.amount * reservation.commodity.volume())
+            .sum();
+        self.inventory.space_left().saturating_sub(reserved_space) / commodity.volume()
     }
 
     // todo list same commodity multiple times for different prices based on inventory
     pub fn price_check_buy_from_store(&self) -> Vec<StoreListing> {
         Commodity::iter()
-            .into_iter()
             .filter_map(|commodity| self.price_check_buy_specific_from_store(commodity))
             .collect()
     }
@@ -187,6 +518,38 @@ impl Store {
 mod tests {
     use super::*;
 
+    /// Fixed price tiers, predictable enough to assert exact totals against
+    struct Tiered;
+
+    impl PricingModel for Tiered {
+        fn mid_price(&self, _: Commodity, conditions: MarketConditions) -> Option<f32> {
+            Some(match conditions.stock {
+                0..=100 => 5.,
+                101..=200 => 2.,
+                _ => 1.,
+            })
+        }
+    }
+
+    /// Tiered, but keeps the last 29 units for the locals
+    struct LocalsFirst;
+
+    impl PricingModel for LocalsFirst {
+        fn mid_price(&self, commodity: Commodity, conditions: MarketConditions) -> Option<f32> {
+            (conditions.stock >= 30)
+                .then(|| Tiered.mid_price(commodity, conditions))
+                .flatten()
+        }
+    }
+
+    fn tiered_store() -> Store {
+        // the tiers are mid prices, the default spread charges a unit more on each of them
+        Store {
+            pricing: Box::new(Tiered),
+            ..default()
+        }
+    }
+
     #[test]
     fn new_store_should_be_empty() {
         let store = Store {
@@ -204,7 +567,7 @@ mod tests {
         };
         assert!(store.list().is_empty());
 
-        store.give(Commodity::Food, 10);
+        store.give(Commodity::Food, 10).expect("Should fit");
         let mut expected = HashMap::new();
         expected.insert(Commodity::Food, 10u64);
         assert_eq!(store.list(), &expected);
@@ -217,10 +580,13 @@ mod tests {
     fn buy_some_food() {
         let mut store = Store {
             inventory: Inventory::default(),
-            ..default()
+            ..tiered_store()
         };
 
-        store.give(Commodity::Food, 100);
+        store.give(Commodity::Food, 100).expect("Should fit");
+        let mut ledger = Ledger::default();
+        let mut wallet = Wallet::default();
+        ledger.mint(&mut wallet, 1000);
 
         let store_listing = store
             .price_check_buy_specific_from_store(Commodity::Food)
@@ -228,19 +594,53 @@ mod tests {
         assert!(store_listing.price > 0);
 
         let receipt = store
-            .buy_from_store(Commodity::Food, 10, Some(store_listing.price))
+            .buy_from_store(
+                Commodity::Food,
+                10,
+                Some(store_listing.price),
+                OrderMode::AllOrNothing,
+                &mut wallet,
+                &mut ledger,
+            )
             .expect("Store should've accepted this sale");
 
         assert_eq!(receipt.commodity, Commodity::Food);
         assert_eq!(receipt.amount, 10);
-        assert_eq!(receipt.price, store_listing.price);
+        assert_eq!(receipt.unit_price, store_listing.price);
+        assert_eq!(receipt.total, store_listing.price * 10);
+        assert_eq!(wallet.credits(), 1000 - receipt.total);
+        assert_eq!(store.wallet.credits(), receipt.total);
+        assert_eq!(ledger.audit([&wallet, &store.wallet]), Ok(()));
     }
 
     #[test]
-    fn sell_some_food() {
+    fn cant_buy_food_without_money() {
         let mut store = Store::default();
+        let mut ledger = Ledger::default();
+        let mut wallet = Wallet::default();
 
-        store.give(Commodity::Food, 100);
+        let receipt = store.buy_from_store(
+            Commodity::Food,
+            10,
+            None,
+            OrderMode::Partial,
+            &mut wallet,
+            &mut ledger,
+        );
+
+        assert!(receipt.is_none());
+        assert_eq!(store.inventory.get(&Commodity::Food), 100);
+        assert_eq!(store.wallet.credits(), 0);
+    }
+
+    #[test]
+    fn sell_some_food() {
+        let mut store = tiered_store();
+
+        store.give(Commodity::Food, 50).expect("Should fit");
+        let mut ledger = Ledger::default();
+        let mut wallet = Wallet::default();
+        ledger.mint(&mut store.wallet, 1000);
 
         let store_listing = store
             .price_check_sell_specific_to_store(Commodity::Food)
@@ -248,11 +648,404 @@ mod tests {
         assert!(store_listing.price > 0);
 
         let receipt = store
-            .sell_to_store(Commodity::Food, 10, Some(store_listing.price))
+            .sell_to_store(
+                Commodity::Food,
+                10,
+                Some(store_listing.price),
+                OrderMode::AllOrNothing,
+                &mut wallet,
+                &mut ledger,
+            )
             .expect("Store should've accepted this sale");
 
         assert_eq!(receipt.commodity, Commodity::Food);
         assert_eq!(receipt.amount, 10);
-        assert_eq!(receipt.price, store_listing.price);
+        assert_eq!(receipt.unit_price, store_listing.price);
+        assert_eq!(wallet.credits(), receipt.total);
+        assert_eq!(ledger.audit([&wallet, &store.wallet]), Ok(()));
+    }
+
+    #[test]
+    fn partial_buy_walks_down_price_tiers() {
+        let mut store = tiered_store();
+        store.give(Commodity::Food, 5).expect("Should fit");
+        let mut ledger = Ledger::default();
+        let mut wallet = Wallet::default();
+        ledger.mint(&mut wallet, 1000);
+
+        let receipt = store
+            .buy_from_store(
+                Commodity::Food,
+                10,
+                None,
+                OrderMode::Partial,
+                &mut wallet,
+                &mut ledger,
+            )
+            .expect("Store should've sold some food");
+
+        // 5 units at 3 while stock is above 100, then 5 units at 6
+        assert_eq!(receipt.amount, 10);
+        assert_eq!(receipt.total, 5 * 3 + 5 * 6);
+        assert_eq!(receipt.unit_price, 4);
+        assert_eq!(store.inventory.get(&Commodity::Food), 95);
+    }
+
+    #[test]
+    fn partial_buy_stops_when_stock_runs_low() {
+        let mut store = Store {
+            inventory: Inventory::default(),
+            pricing: Box::new(LocalsFirst),
+            ..tiered_store()
+        };
+        store.give(Commodity::Food, 35).expect("Should fit");
+        let mut ledger = Ledger::default();
+        let mut wallet = Wallet::default();
+        ledger.mint(&mut wallet, 1000);
+
+        let all_or_nothing = store.buy_from_store(
+            Commodity::Food,
+            10,
+            None,
+            OrderMode::AllOrNothing,
+            &mut wallet,
+            &mut ledger,
+        );
+        assert!(all_or_nothing.is_none());
+
+        let receipt = store
+            .buy_from_store(
+                Commodity::Food,
+                10,
+                None,
+                OrderMode::Partial,
+                &mut wallet,
+                &mut ledger,
+            )
+            .expect("Store should've sold some food");
+
+        assert_eq!(receipt.amount, 6);
+        assert_eq!(receipt.total, 6 * 6);
+        assert_eq!(store.inventory.get(&Commodity::Food), 29);
+    }
+
+    #[test]
+    fn partial_buy_stops_when_money_runs_out() {
+        let mut store = tiered_store();
+        let mut ledger = Ledger::default();
+        let mut wallet = Wallet::default();
+        ledger.mint(&mut wallet, 14);
+
+        let receipt = store
+            .buy_from_store(
+                Commodity::Food,
+                10,
+                None,
+                OrderMode::Partial,
+                &mut wallet,
+                &mut ledger,
+            )
+            .expect("Should afford some food");
+
+        assert_eq!(receipt.amount, 2);
+        assert_eq!(wallet.credits(), 2);
+    }
+
+    #[test]
+    fn store_wont_buy_more_than_fits_in_warehouse() {
+        let mut store = Store {
+            inventory: Inventory::with_capacity(15),
+            ..default()
+        };
+        store.give(Commodity::Fuel, 10).expect("Should fit");
+        let mut ledger = Ledger::default();
+        let mut wallet = Wallet::default();
+        ledger.mint(&mut store.wallet, 1000);
+
+        let receipt = store
+            .sell_to_store(
+                Commodity::Food,
+                10,
+                None,
+                OrderMode::Partial,
+                &mut wallet,
+                &mut ledger,
+            )
+            .expect("Store should've bought some food");
+
+        assert_eq!(receipt.amount, 5);
+        assert_eq!(store.inventory.space_left(), 0);
+    }
+
+    #[test]
+    fn buying_raises_the_consumption_rate() {
+        let mut store = Store::default();
+        let mut ledger = Ledger::default();
+        let mut wallet = Wallet::default();
+        ledger.mint(&mut wallet, 1000);
+        let price_before = store
+            .price_check_buy_specific_from_store(Commodity::Food)
+            .expect("Should sell food")
+            .price;
+
+        store
+            .buy_from_store(
+                Commodity::Food,
+                10,
+                None,
+                OrderMode::AllOrNothing,
+                &mut wallet,
+                &mut ledger,
+            )
+            .expect("Should afford food");
+        assert_eq!(store.consumption_rate(Commodity::Food), 0.);
+
+        store.end_consumption_period();
+        assert!(store.consumption_rate(Commodity::Food) > 0.);
+
+        let price_after = store
+            .price_check_buy_specific_from_store(Commodity::Food)
+            .expect("Should sell food")
+            .price;
+        assert!(price_after > price_before);
+    }
+
+    /// Random stores, seeded so failures can be reproduced
+    fn random_stores(cases: usize) -> impl Iterator<Item = (Store, Commodity, Amount)> {
+        let mut rng = fastrand::Rng::with_seed(6);
+        let commodities: Vec<Commodity> = Commodity::iter().collect();
+        (0..cases).map(move |_| {
+            let spread = 0.001 + rng.f32();
+            let commodity = commodities[rng.usize(..commodities.len())];
+            let stock = rng.u64(1..1_000);
+            let mut store = Store {
+                inventory: Inventory::with_capacity(10_000),
+                ..default()
+            }
+            .with_spread(spread);
+            store.give(commodity, stock).expect("Should fit");
+            (store, commodity, rng.u64(1..600))
+        })
+    }
+
+    #[test]
+    fn store_never_buys_for_more_than_it_sells() {
+        for (store, commodity, _) in random_stores(2_000) {
+            let ask = store.price_check_buy_specific_from_store(commodity);
+            let bid = store.price_check_sell_specific_to_store(commodity);
+
+            if let (Some(ask), Some(bid)) = (ask, bid) {
+                assert!(
+                    bid.price < ask.price,
+                    "{:?} at stock {} with spread {}: bid {} ask {}",
+                    commodity,
+                    store.inventory.get(&commodity),
+                    store.spread,
+                    bid.price,
+                    ask.price
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip_through_one_store_is_never_profitable() {
+        for (mut store, commodity, amount) in random_stores(2_000) {
+            let stock = store.inventory.get(&commodity);
+            let mut ledger = Ledger::default();
+            let mut wallet = Wallet::default();
+            ledger.mint(&mut wallet, 100_000);
+            ledger.mint(&mut store.wallet, 100_000);
+
+            let bought = store
+                .buy_from_store(
+                    commodity,
+                    amount,
+                    None,
+                    OrderMode::Partial,
+                    &mut wallet,
+                    &mut ledger,
+                )
+                .expect("Store has stock and we have money");
+            let sold = store
+                .sell_to_store(
+                    commodity,
+                    bought.amount,
+                    None,
+                    OrderMode::Partial,
+                    &mut wallet,
+                    &mut ledger,
+                )
+                .map(|receipt| receipt.total)
+                .unwrap_or(0);
+
+            assert!(
+                sold < bought.total,
+                "{:?} at stock {} with spread {}: bought {} for {}, sold for {}",
+                commodity,
+                stock,
+                store.spread,
+                bought.amount,
+                bought.total,
+                sold
+            );
+        }
+    }
+
+    #[test]
+    fn bid_stays_below_ask_without_a_spread() {
+        for stock in 1..300 {
+            let mut store = tiered_store();
+            store.take(Commodity::Food, 100);
+            store.give(Commodity::Food, stock).expect("Should fit");
+
+            let ask = store.price_check_buy_specific_from_store(Commodity::Food);
+            let bid = store.price_check_sell_specific_to_store(Commodity::Food);
+
+            if let (Some(ask), Some(bid)) = (ask, bid) {
+                assert!(bid.price < ask.price, "At stock {}", stock);
+            }
+        }
+    }
+
+    #[test]
+    fn ask_never_goes_above_the_ceiling() {
+        let store = Store {
+            inventory: Inventory::default(),
+            ..default()
+        };
+        let ceiling = store
+            .pricing
+            .max_price(Commodity::Food)
+            .expect("Food has a ceiling");
+        let out_of_stock = store
+            .pricing
+            .mid_price(Commodity::Food, store.market_conditions(Commodity::Food, 0))
+            .expect("Food has a price");
+
+        assert!(store.ask(Commodity::Food, out_of_stock) <= ceiling);
+        assert!(store.buy_price(Commodity::Food, 1).expect("Food in stock") <= ceiling);
+    }
+
+    #[test]
+    #[should_panic]
+    fn spread_has_to_be_positive() {
+        let _ = Store::default().with_spread(0.);
+    }
+
+    #[test]
+    fn quote_integrates_price_over_quantity() {
+        let mut store = tiered_store();
+        store.give(Commodity::Food, 5).expect("Should fit");
+
+        let quote = store
+            .quote_buy(Commodity::Food, 10)
+            .expect("Store has food");
+
+        assert_eq!(quote.amount, 10);
+        assert_eq!(quote.total, 5 * 3 + 5 * 6);
+        assert_eq!(quote.average_price(), 4);
+        assert_eq!(quote.marginal_price, 6);
+        // quoting doesn't trade
+        assert_eq!(store.inventory.get(&Commodity::Food), 105);
+    }
+
+    #[test]
+    fn quote_is_limited_by_stock_and_budget() {
+        let store = tiered_store();
+
+        let everything = store
+            .quote_buy(Commodity::Food, 1000)
+            .expect("Store has food");
+        assert_eq!(everything.amount, 100);
+
+        let affordable = store
+            .quote_buy_with_budget(Commodity::Food, 1000, 14)
+            .expect("Can afford some food");
+        assert_eq!(affordable.amount, 2);
+        assert_eq!(affordable.total, 12);
+    }
+
+    #[test]
+    fn listings_dont_promise_more_than_is_in_stock() {
+        let mut store = Store {
+            inventory: Inventory::default(),
+            ..default()
+        };
+        store.give(Commodity::Food, 5).expect("Should fit");
+
+        let listing = store
+            .price_check_buy_specific_from_store(Commodity::Food)
+            .expect("Store has food");
+
+        assert_eq!(listing.amount, 5);
+    }
+
+    #[test]
+    fn reserved_stock_cant_be_bought_by_others() {
+        let mut store = tiered_store();
+        let mut ledger = Ledger::default();
+        let mut wallet = Wallet::default();
+        ledger.mint(&mut wallet, 1000);
+
+        let reservation = store.reserve(Side::Buy, Commodity::Food, 95, 10.);
+
+        let quote = store
+            .quote_buy(Commodity::Food, 100)
+            .expect("Some food isn't reserved");
+        assert_eq!(quote.amount, 5);
+        let listing = store
+            .price_check_buy_specific_from_store(Commodity::Food)
+            .expect("Some food isn't reserved");
+        assert_eq!(listing.amount, 5);
+        let receipt = store
+            .buy_from_store(
+                Commodity::Food,
+                10,
+                None,
+                OrderMode::Partial,
+                &mut wallet,
+                &mut ledger,
+            )
+            .expect("Some food isn't reserved");
+        assert_eq!(receipt.amount, 5);
+
+        store.release(reservation);
+        assert_eq!(
+            store
+                .quote_buy(Commodity::Food, 100)
+                .expect("Food is back")
+                .amount,
+            95
+        );
+    }
+
+    #[test]
+    fn reserved_warehouse_space_cant_be_sold_into() {
+        let mut store = Store {
+            inventory: Inventory::with_capacity(10),
+            ..tiered_store()
+        };
+        Ledger::default().mint(&mut store.wallet, 1000);
+
+        store.reserve(Side::Sell, Commodity::Fuel, 8, 10.);
+
+        let quote = store
+            .quote_sell(Commodity::Food, 100)
+            .expect("Some space isn't reserved");
+        assert_eq!(quote.amount, 2);
+    }
+
+    #[test]
+    fn reservations_expire() {
+        let mut store = tiered_store();
+        store.reserve(Side::Buy, Commodity::Food, 100, 10.);
+        assert!(store.quote_buy(Commodity::Food, 1).is_none());
+
+        store.remove_expired_reservations(5.);
+        assert!(store.quote_buy(Commodity::Food, 1).is_none());
+
+        store.remove_expired_reservations(10.);
+        assert!(store.quote_buy(Commodity::Food, 1).is_some());
     }
 }